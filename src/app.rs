@@ -1,10 +1,35 @@
 use std::time::Duration;
 
-use bevy::{app::AppExit, prelude::*};
+use avian3d::prelude::*;
+use bevy::{
+    app::AppExit,
+    color::palettes::css::GOLD,
+    input::InputPlugin,
+    platform::collections::HashMap,
+    prelude::*,
+    scene::ScenePlugin,
+    state::app::StatesPlugin,
+    time::{TimeSystem, TimeUpdateStrategy},
+};
+use rand::{prelude::*, rngs::StdRng};
+use thiserror::Error;
 
 use crate::{
+    boss::BossPlugin,
+    bot::KitingBot,
     debug_ui::DebugUi,
+    npc::{NpcHandles, NpcPlugin},
+    physics::MainPhysicsPlugin,
+    player::{
+        PcHandles, PlayerCharacter, PlayerCharactersAsset, PlayerControl, PlayerPlugin,
+        SelectedCharacter,
+    },
+    replay::{ReplayPlugin, save_replay},
+    save::{SaveFile, SavePlugin, SaveSettings, write_save},
+    skills::{BonusMeta, Level, Skill, SkillPluginGroup, Skills, Upgrade},
+    terrain::TerrainPlugin,
     ui::{BUTTON_HOVERED_COLOR, BUTTON_NORMAL_COLOR, BUTTON_PRESSED_COLOR, INFINITE_TEMP_COLOR},
+    waves::{SpawnDirector, WavesPlugin},
 };
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
@@ -36,25 +61,57 @@ impl ComputedStates for InGame {
     }
 }
 
+/// App states, state scoped entities and the [`RunState`], shared by windowed and headless apps.
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .add_computed_state::<InGame>()
+            .enable_state_scoped_entities::<AppState>()
+            .enable_state_scoped_entities::<InGame>()
+            .init_resource::<RunState>()
+            .init_resource::<RunStats>()
+            .init_resource::<RunSeed>()
+            .insert_resource(RunRng::new(0))
+            .add_systems(First, pause_game_time.before(TimeSystem))
+            .add_systems(Update, update_run_time.run_if(in_state(AppState::Run)))
+            .add_systems(
+                OnTransition {
+                    exited: AppState::Menu,
+                    entered: AppState::Run,
+                },
+                start_run,
+            );
+    }
+}
+
+/// Marks apps built by [`build_headless_app`], which skip mesh and material setup.
+#[derive(Resource)]
+pub struct Headless;
+
 pub struct MainMenuPlugin;
 
 impl Plugin for MainMenuPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<RunState>()
-            .add_systems(OnEnter(AppState::Menu), setup_menu)
+        app.add_systems(OnEnter(AppState::Menu), setup_menu)
             .add_systems(
                 Update,
                 (
                     update_app_state,
-                    update_menu.run_if(in_state(AppState::Menu)),
+                    (
+                        update_menu,
+                        buy_with_currency,
+                        edit_run_seed,
+                        update_seed_text,
+                        update_progress_text,
+                        build_character_list,
+                        build_bonus_list,
+                        cycle_selected_character,
+                        update_character_cards,
+                    )
+                        .run_if(in_state(AppState::Menu)),
                 ),
-            )
-            .add_systems(
-                OnTransition {
-                    exited: AppState::Menu,
-                    entered: AppState::Run,
-                },
-                start_run,
             );
     }
 }
@@ -63,19 +120,125 @@ impl Plugin for MainMenuPlugin {
 pub struct RunState {
     pub run_time: Duration,
     pub live_npcs: u32,
+    pub bosses_killed: u32,
 }
 
-fn start_run(mut run_state: ResMut<RunState>) {
+/// Statistics of the player in the current run, listed on the results screen.
+#[derive(Resource, Default)]
+pub struct RunStats {
+    /// Level reached, counting the level ups taken.
+    pub level: u32,
+    pub xp: u32,
+    /// NPCs killed, by name.
+    pub kills: HashMap<String, u32>,
+    /// Kills per skill of the player.
+    pub skill_kills: HashMap<Skill, u32>,
+    /// Damage dealt per skill of the player, after resistances.
+    pub damage_dealt: HashMap<Skill, f32>,
+    /// Damage taken per skill of the attackers, after resistances.
+    pub damage_taken: HashMap<Skill, f32>,
+    /// Skills and passives of the player, as of its last upgrade or its death.
+    pub equipped: Vec<(Upgrade, Level)>,
+    /// Earned when the run ended, added to the [`SaveFile`].
+    pub currency: u32,
+}
+
+/// Seed of the gameplay randomness, so that a seed and the same player input replay a run.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunSeed(pub u64);
+
+impl Default for RunSeed {
+    fn default() -> Self {
+        Self(thread_rng().next_u64())
+    }
+}
+
+/// Random number generators of the gameplay systems, reseeded from the [`RunSeed`] when a
+/// run starts. Each system draws from its own stream, so the order in which systems run
+/// doesn't change the results.
+#[derive(Resource)]
+pub struct RunRng {
+    pub spawn: StdRng,
+    pub upgrades: StdRng,
+    pub swarm: StdRng,
+    pub waves: StdRng,
+    pub combat: StdRng,
+    pub loot: StdRng,
+}
+
+impl RunRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            spawn: StdRng::seed_from_u64(seed),
+            upgrades: StdRng::seed_from_u64(seed.wrapping_add(1)),
+            swarm: StdRng::seed_from_u64(seed.wrapping_add(2)),
+            waves: StdRng::seed_from_u64(seed.wrapping_add(3)),
+            combat: StdRng::seed_from_u64(seed.wrapping_add(4)),
+            loot: StdRng::seed_from_u64(seed.wrapping_add(5)),
+        }
+    }
+}
+
+fn start_run(
+    mut run_state: ResMut<RunState>,
+    mut run_stats: ResMut<RunStats>,
+    seed: Res<RunSeed>,
+    mut rng: ResMut<RunRng>,
+    mut fixed: ResMut<Time<Fixed>>,
+) {
     run_state.run_time = Duration::ZERO;
     run_state.live_npcs = 0;
+    run_state.bosses_killed = 0;
+    *run_stats = RunStats::default();
+    *rng = RunRng::new(seed.0);
+    // the fixed ticks of a run don't depend on what was left of the previous one
+    let overstep = fixed.overstep();
+    fixed.discard_overstep(overstep);
+}
+
+/// State of the rest of the frame, once the pending transition is applied.
+fn frame_state(app_state: &State<AppState>, next_state: &NextState<AppState>) -> AppState {
+    match *next_state {
+        NextState::Pending(state) => state,
+        NextState::Unchanged => *app_state.get(),
+    }
+}
+
+/// Whether the frame ends up in [`AppState::Run`], for systems running before the state
+/// transition.
+pub(crate) fn frame_in_run(
+    app_state: Res<State<AppState>>,
+    next_state: Res<NextState<AppState>>,
+) -> bool {
+    frame_state(&app_state, &next_state) == AppState::Run
+}
+
+/// Game time stops in frames that end up in the menu, or in a paused or upgrading run, so
+/// the fixed ticks and cooldowns of a run don't depend on the time spent there, and a
+/// [`Replay`](crate::replay::Replay) can play them back with the same durations.
+pub(crate) fn pause_game_time(
+    app_state: Res<State<AppState>>,
+    next_state: Res<NextState<AppState>>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    let state = frame_state(&app_state, &next_state);
+    if matches!(state, AppState::Menu | AppState::Paused | AppState::Upgrade) {
+        if !time.is_paused() {
+            time.pause();
+        }
+    } else if time.is_paused() {
+        time.unpause();
+    }
+}
+
+pub(crate) fn update_run_time(time: Res<Time>, mut run_state: ResMut<RunState>) {
+    run_state.run_time += time.delta();
 }
 
 fn update_app_state(
     keyboard: Res<ButtonInput<KeyCode>>,
-    time: Res<Time>,
     app_state: Res<State<AppState>>,
     mut next_state: ResMut<NextState<AppState>>,
-    mut run_state: ResMut<RunState>,
     debug_ui: Res<DebugUi>,
     mut exit: EventWriter<AppExit>,
 ) {
@@ -109,8 +272,6 @@ fn update_app_state(
         AppState::Run => {
             if esc || enter {
                 next_state.set(AppState::Paused);
-            } else {
-                run_state.run_time += time.delta();
             }
         }
     }
@@ -119,6 +280,31 @@ fn update_app_state(
 #[derive(Component)]
 struct MainMenuUi;
 
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum MainMenuButton {
+    Run,
+    Seed,
+    /// Index into [`PlayerCharactersAsset`].
+    Character(usize),
+    /// Index into the bonuses of [`Skills`], sorted by name.
+    Bonus(usize),
+}
+
+/// Parent of the character cards, filled once the characters are loaded.
+#[derive(Component)]
+struct CharacterList;
+
+/// Parent of the buttons buying starting bonuses.
+#[derive(Component)]
+struct BonusList;
+
+#[derive(Component)]
+struct SeedText;
+
+/// Currency and lifetime stats of the [`SaveFile`].
+#[derive(Component)]
+struct ProgressText;
+
 fn setup_menu(mut cmd: Commands) {
     cmd.spawn((
         Node {
@@ -127,12 +313,70 @@ fn setup_menu(mut cmd: Commands) {
             height: Val::Percent(100.),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
+            flex_direction: FlexDirection::Column,
             ..default()
         },
         MainMenuUi,
         StateScoped(AppState::Menu),
     ))
     .with_children(|parent| {
+        parent.spawn((
+            Node {
+                margin: UiRect::bottom(Val::Px(30.)),
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Stretch,
+                ..default()
+            },
+            CharacterList,
+        ));
+
+        parent.spawn((
+            Text::new("click a character or use the arrow keys to pick one"),
+            Node {
+                margin: UiRect::bottom(Val::Px(30.)),
+                ..default()
+            },
+            TextFont {
+                font_size: 15.0,
+                ..default()
+            },
+        ));
+
+        parent.spawn((
+            Text::default(),
+            Node {
+                margin: UiRect::bottom(Val::Px(10.)),
+                ..default()
+            },
+            TextFont {
+                font_size: 20.0,
+                ..default()
+            },
+            TextColor(GOLD.into()),
+            ProgressText,
+        ));
+
+        parent.spawn((
+            Node {
+                margin: UiRect::bottom(Val::Px(10.)),
+                flex_direction: FlexDirection::Row,
+                ..default()
+            },
+            BonusList,
+        ));
+
+        parent.spawn((
+            Text::new("click a bonus to buy its next level, added at the start of every run"),
+            Node {
+                margin: UiRect::bottom(Val::Px(30.)),
+                ..default()
+            },
+            TextFont {
+                font_size: 15.0,
+                ..default()
+            },
+        ));
+
         parent
             .spawn((
                 Button,
@@ -143,8 +387,9 @@ fn setup_menu(mut cmd: Commands) {
                     align_items: AlignItems::Center,
                     ..default()
                 },
-                BackgroundColor(BUTTON_NORMAL_COLOR.into()),
+                BackgroundColor(BUTTON_NORMAL_COLOR),
                 BorderRadius::all(Val::Px(20.0)),
+                MainMenuButton::Run,
             ))
             .with_children(|parent| {
                 parent.spawn((
@@ -156,21 +401,64 @@ fn setup_menu(mut cmd: Commands) {
                     TextColor(INFINITE_TEMP_COLOR),
                 ));
             });
+
+        parent
+            .spawn((
+                Button,
+                Node {
+                    margin: UiRect::top(Val::Px(30.)),
+                    padding: UiRect::axes(Val::Px(20.), Val::Px(10.)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                BackgroundColor(BUTTON_NORMAL_COLOR),
+                BorderRadius::all(Val::Px(10.0)),
+                MainMenuButton::Seed,
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Text::default(),
+                    TextFont {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                    TextColor(INFINITE_TEMP_COLOR),
+                    SeedText,
+                ));
+            });
+
+        parent.spawn((
+            Text::new("type digits to enter a seed, click it for a random one"),
+            Node {
+                margin: UiRect::top(Val::Px(10.)),
+                ..default()
+            },
+            TextFont {
+                font_size: 15.0,
+                ..default()
+            },
+        ));
     });
 }
 
 fn update_menu(
     mut next_state: ResMut<NextState<AppState>>,
+    mut seed: ResMut<RunSeed>,
     mut q_interaction: Query<
-        (&Interaction, &mut BackgroundColor),
-        (Changed<Interaction>, With<Button>),
+        (&Interaction, &mut BackgroundColor, &MainMenuButton),
+        Changed<Interaction>,
     >,
 ) {
-    for (interaction, mut color) in &mut q_interaction {
+    for (interaction, mut color, button) in &mut q_interaction {
         match *interaction {
             Interaction::Pressed => {
                 *color = BUTTON_PRESSED_COLOR.into();
-                next_state.set(AppState::Run);
+                match button {
+                    MainMenuButton::Run => next_state.set(AppState::Run),
+                    MainMenuButton::Seed => *seed = RunSeed::default(),
+                    MainMenuButton::Character(_) | MainMenuButton::Bonus(_) => {}
+                }
             }
             Interaction::Hovered => {
                 *color = BUTTON_HOVERED_COLOR.into();
@@ -181,3 +469,460 @@ fn update_menu(
         }
     }
 }
+
+/// Selects unlocked characters, and spends currency on locked ones and on bonuses.
+fn buy_with_currency(
+    pc_handles: Res<PcHandles>,
+    pc_assets: Res<Assets<PlayerCharactersAsset>>,
+    skills: Res<Skills>,
+    settings: Res<SaveSettings>,
+    mut save: ResMut<SaveFile>,
+    mut selected: ResMut<SelectedCharacter>,
+    q_interaction: Query<(&Interaction, &MainMenuButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in &q_interaction {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *button {
+            MainMenuButton::Character(idx) => {
+                let Some(pc) = pc_assets
+                    .get(&pc_handles.config)
+                    .and_then(|pcs| pcs.0.get(idx))
+                else {
+                    continue;
+                };
+                if !save.is_unlocked(pc) {
+                    if !save.spend(pc.unlock_cost) {
+                        continue;
+                    }
+                    save.unlocked.insert(pc.name.clone());
+                    write_save(&save, &settings);
+                }
+                selected.0 = idx;
+            }
+            MainMenuButton::Bonus(idx) => {
+                let Some((name, bonus)) = sorted_bonuses(&skills).get(idx).copied() else {
+                    continue;
+                };
+                let level = save.bonuses.get(name).copied().unwrap_or_default();
+                let Some(cost) = bonus.costs.get(level as usize).copied() else {
+                    continue;
+                };
+                if save.spend(cost) {
+                    save.bonuses.insert(name.clone(), level + 1);
+                    write_save(&save, &settings);
+                }
+            }
+            MainMenuButton::Run | MainMenuButton::Seed => {}
+        }
+    }
+}
+
+const DIGIT_KEYS: [KeyCode; 10] = [
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+fn edit_run_seed(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    debug_ui: Res<DebugUi>,
+    mut seed: ResMut<RunSeed>,
+) {
+    if debug_ui.has_focus() {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Backspace) {
+        seed.0 /= 10;
+    }
+    for (digit, key) in DIGIT_KEYS.iter().enumerate() {
+        if !keyboard.just_pressed(*key) {
+            continue;
+        }
+        if let Some(new_seed) = seed
+            .0
+            .checked_mul(10)
+            .and_then(|s| s.checked_add(digit as u64))
+        {
+            seed.0 = new_seed;
+        }
+    }
+}
+
+fn update_seed_text(seed: Res<RunSeed>, mut q_txt: Query<&mut Text, With<SeedText>>) {
+    for mut txt in &mut q_txt {
+        if seed.is_changed() || txt.0.is_empty() {
+            txt.0 = format!("SEED {}", seed.0);
+        }
+    }
+}
+
+fn update_progress_text(save: Res<SaveFile>, mut q_txt: Query<&mut Text, With<ProgressText>>) {
+    for mut txt in &mut q_txt {
+        if save.is_changed() || txt.0.is_empty() {
+            let stats = &save.stats;
+            txt.0 = format!(
+                "{} coins | {} runs, {} wins, {} kills, {} bosses, best level {}, {:.0} min played",
+                save.currency,
+                stats.runs,
+                stats.wins,
+                stats.kills,
+                stats.bosses_killed,
+                stats.best_level,
+                stats.run_time / 60.,
+            );
+        }
+    }
+}
+
+fn add_character_card(
+    parent: &mut ChildSpawnerCommands<'_>,
+    skills: &Skills,
+    index: usize,
+    pc: &PlayerCharacter,
+    locked: bool,
+) {
+    let mut default_skills = pc
+        .default_skills
+        .iter()
+        .map(|(skill, spec)| {
+            format!(
+                "{}: {}",
+                skills.ui_name(*skill).unwrap_or("?"),
+                skills.spec_ui_text(spec)
+            )
+        })
+        .collect::<Vec<_>>();
+    default_skills.sort();
+    let selected_skills = pc
+        .selected_skills
+        .iter()
+        .map(|skill| skills.ui_name(*skill).unwrap_or("?"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    parent
+        .spawn((
+            Button,
+            Node {
+                width: Val::Px(260.),
+                margin: UiRect::horizontal(Val::Px(10.)),
+                padding: UiRect::all(Val::Px(15.)),
+                border: UiRect::all(Val::Px(3.)),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            BackgroundColor(BUTTON_NORMAL_COLOR),
+            BorderColor(BUTTON_NORMAL_COLOR),
+            BorderRadius::all(Val::Px(10.0)),
+            MainMenuButton::Character(index),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text(pc.name.clone()),
+                Node {
+                    margin: UiRect::bottom(Val::Px(10.)),
+                    ..default()
+                },
+                TextFont {
+                    font_size: 25.0,
+                    ..default()
+                },
+                TextColor(INFINITE_TEMP_COLOR),
+            ));
+            let lines = [format!("Speed: {}", pc.speed)]
+                .into_iter()
+                .chain(default_skills)
+                .chain([format!("Selected: {selected_skills}")])
+                .chain(locked.then(|| format!("Locked: unlock for {} coins", pc.unlock_cost)));
+            for line in lines {
+                parent.spawn((
+                    Text(line),
+                    TextFont {
+                        font_size: 15.0,
+                        ..default()
+                    },
+                ));
+            }
+        });
+}
+
+fn build_character_list(
+    pc_handles: Res<PcHandles>,
+    pc_assets: Res<Assets<PlayerCharactersAsset>>,
+    skills: Res<Skills>,
+    save: Res<SaveFile>,
+    mut pc_events: EventReader<AssetEvent<PlayerCharactersAsset>>,
+    q_list: Query<(Entity, Option<&Children>), With<CharacterList>>,
+    mut cmd: Commands,
+) {
+    let reloaded = pc_events.read().count() > 0 || skills.is_changed() || save.is_changed();
+    let Ok((list, children)) = q_list.single() else {
+        return;
+    };
+    if children.is_some_and(|children| !children.is_empty()) && !reloaded {
+        return;
+    }
+    let Some(pcs) = pc_assets.get(&pc_handles.config) else {
+        return;
+    };
+    if skills.skills.is_empty() {
+        return;
+    }
+    cmd.entity(list)
+        .despawn_related::<Children>()
+        .with_children(|parent| {
+            for (index, pc) in pcs.0.iter().enumerate() {
+                add_character_card(parent, &skills, index, pc, !save.is_unlocked(pc));
+            }
+        });
+}
+
+/// Bonuses of `skills` in a stable order, indexed by [`MainMenuButton::Bonus`].
+fn sorted_bonuses(skills: &Skills) -> Vec<(&String, &BonusMeta)> {
+    let mut bonuses = skills.bonuses.iter().collect::<Vec<_>>();
+    bonuses.sort_by_key(|(name, _)| *name);
+    bonuses
+}
+
+fn build_bonus_list(
+    skills: Res<Skills>,
+    save: Res<SaveFile>,
+    q_list: Query<(Entity, Option<&Children>), With<BonusList>>,
+    mut cmd: Commands,
+) {
+    let Ok((list, children)) = q_list.single() else {
+        return;
+    };
+    if children.is_some_and(|children| !children.is_empty())
+        && !skills.is_changed()
+        && !save.is_changed()
+    {
+        return;
+    }
+    cmd.entity(list)
+        .despawn_related::<Children>()
+        .with_children(|parent| {
+            for (index, (name, bonus)) in sorted_bonuses(&skills).into_iter().enumerate() {
+                let level = save.bonuses.get(name).copied().unwrap_or_default();
+                let max_level = bonus.costs.len() as u32;
+                let cost = match bonus.costs.get(level as usize) {
+                    Some(cost) => format!("{cost} coins"),
+                    None => "max".to_string(),
+                };
+                parent
+                    .spawn((
+                        Button,
+                        Node {
+                            margin: UiRect::horizontal(Val::Px(10.)),
+                            padding: UiRect::axes(Val::Px(15.), Val::Px(10.)),
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(BUTTON_NORMAL_COLOR),
+                        BorderRadius::all(Val::Px(10.0)),
+                        MainMenuButton::Bonus(index),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Text(format!("{} {level}/{max_level}", bonus.ui_name)),
+                            TextFont {
+                                font_size: 20.0,
+                                ..default()
+                            },
+                            TextColor(INFINITE_TEMP_COLOR),
+                        ));
+                        parent.spawn((
+                            Text(cost),
+                            TextFont {
+                                font_size: 15.0,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn cycle_selected_character(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    debug_ui: Res<DebugUi>,
+    pc_handles: Res<PcHandles>,
+    pc_assets: Res<Assets<PlayerCharactersAsset>>,
+    save: Res<SaveFile>,
+    mut selected: ResMut<SelectedCharacter>,
+) {
+    let Some(pcs) = pc_assets.get(&pc_handles.config) else {
+        return;
+    };
+    let count = pcs.0.len();
+    let unlocked = |idx: usize| save.is_unlocked(&pcs.0[idx]);
+    // a locked `--character` stays selected, it's replaced when the run starts
+    if selected.0 >= count
+        && let Some(idx) = (0..count).find(|idx| unlocked(*idx))
+    {
+        selected.0 = idx;
+    }
+    if debug_ui.has_focus() {
+        return;
+    }
+    let step = if keyboard.just_pressed(KeyCode::ArrowRight) {
+        1
+    } else if keyboard.just_pressed(KeyCode::ArrowLeft) {
+        count - 1
+    } else {
+        return;
+    };
+    let mut idx = selected.0;
+    // skips locked characters, staying put when all others are locked
+    for _ in 0..count {
+        idx = (idx + step) % count;
+        if unlocked(idx) {
+            selected.0 = idx;
+            return;
+        }
+    }
+}
+
+fn update_character_cards(
+    selected: Res<SelectedCharacter>,
+    mut q_cards: Query<(&MainMenuButton, &mut BorderColor)>,
+) {
+    for (button, mut border) in &mut q_cards {
+        let MainMenuButton::Character(idx) = button else {
+            continue;
+        };
+        let color = if *idx == selected.0 {
+            GOLD.into()
+        } else {
+            BUTTON_NORMAL_COLOR
+        };
+        if border.0 != color {
+            border.0 = color;
+        }
+    }
+}
+
+// headless
+
+/// Builds an app that runs the full game simulation without a window or GPU.
+///
+/// Every `update` advances the game by exactly one `timestep`, which is also used as the
+/// fixed timestep, so runs don't depend on wall clock time. The player is controlled by a
+/// [`KitingBot`], replace the [`PlayerControl`] resource to use another policy. Add a
+/// `LogPlugin` for logs, only once per process since it sets the global logger.
+pub fn build_headless_app(timestep: Duration) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        StatesPlugin,
+        TransformPlugin,
+        InputPlugin,
+        ScenePlugin,
+    ))
+    .init_asset::<Mesh>()
+    .init_asset::<StandardMaterial>()
+    .insert_resource(Headless)
+    .insert_resource(TimeUpdateStrategy::ManualDuration(timestep))
+    .insert_resource(Time::<Fixed>::from_duration(timestep))
+    .init_resource::<DebugUi>()
+    .insert_resource(PlayerControl::Bot(Box::new(KitingBot)))
+    .add_plugins(PhysicsPlugins::default())
+    .add_plugins((
+        GameStatePlugin,
+        MainPhysicsPlugin,
+        TerrainPlugin,
+        PlayerPlugin,
+        NpcPlugin,
+        BossPlugin,
+        SkillPluginGroup,
+        WavesPlugin,
+        ReplayPlugin,
+        SavePlugin,
+    ))
+    .insert_resource(SaveSettings { path: None });
+    app
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum HeadlessError {
+    #[error("Failed to load {0}")]
+    AssetLoad(String),
+}
+
+/// Waits for the game assets to load, then starts a run and steps it until it is won or lost,
+/// or until `max_run_time` of game time has passed. Returns the final [`AppState`].
+///
+/// The run's replay is saved in either case.
+pub fn run_headless(app: &mut App, max_run_time: Duration) -> Result<AppState, HeadlessError> {
+    app.finish();
+    app.cleanup();
+    app.update();
+    let handles = {
+        let world = app.world();
+        [
+            world.resource::<Skills>().handle.clone().untyped(),
+            world.resource::<NpcHandles>().config.clone().untyped(),
+            world.resource::<PcHandles>().config.clone().untyped(),
+            world.resource::<SpawnDirector>().config.clone().untyped(),
+        ]
+    };
+    loop {
+        let asset_server = app.world().resource::<AssetServer>();
+        if let Some(handle) = handles
+            .iter()
+            .find(|h| asset_server.load_state(h.id()).is_failed())
+        {
+            let path = handle.path().map(ToString::to_string).unwrap_or_default();
+            return Err(HeadlessError::AssetLoad(path));
+        }
+        if handles
+            .iter()
+            .all(|h| asset_server.is_loaded_with_dependencies(h.id()))
+        {
+            break;
+        }
+        app.update();
+    }
+
+    info!(
+        "Starting headless run with seed {}.",
+        app.world().resource::<RunSeed>().0
+    );
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Run);
+    loop {
+        app.update();
+        let state = *app.world().resource::<State<AppState>>().get();
+        let run_time = app.world().resource::<RunState>().run_time;
+        if matches!(state, AppState::Won | AppState::Lost) {
+            info!(
+                "Headless run took {:.3}s of game time.",
+                run_time.as_secs_f32()
+            );
+            return Ok(state);
+        }
+        if run_time >= max_run_time {
+            info!(
+                "Headless run timed out after {:.3}s of game time.",
+                run_time.as_secs_f32()
+            );
+            if let Err(err) = app.world_mut().run_system_cached(save_replay) {
+                error!("Failed to run save_replay: {err}");
+            }
+            return Ok(state);
+        }
+    }
+}
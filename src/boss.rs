@@ -0,0 +1,188 @@
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::{
+    app::AppState,
+    skills::{
+        ApplySkillSpecs, Level, RemovedSkills, Skill, SkillSpec, SkillSpecs,
+        health::{Health, MaxHealth, die},
+    },
+};
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<BossSpec>().add_systems(
+            Update,
+            // on the health left after this frame's damage
+            update_boss_phase
+                .after(die)
+                .before(ApplySkillSpecs)
+                .run_if(in_state(AppState::Run)),
+        );
+    }
+}
+
+/// Boss section of an NPC in `all.npcs.ron`.
+#[derive(Reflect, Clone, Debug, Deserialize)]
+pub struct BossSpec {
+    /// Phases ordered by decreasing `health`.
+    pub phases: Vec<BossPhase>,
+}
+
+#[derive(Reflect, Clone, Debug, Deserialize)]
+pub struct BossPhase {
+    /// Fraction of the max health at or below which the phase starts.
+    pub health: f32,
+    /// Skills the boss stops using when the phase starts, before `skills` are applied. A
+    /// removed skill that is also in `skills` starts over from the phase's specs.
+    #[serde(default)]
+    pub remove: Vec<Skill>,
+    /// Skill specs applied on top of the NPC's skills when the phase starts.
+    pub skills: HashMap<Skill, SkillSpec>,
+}
+
+#[derive(Component)]
+pub struct Boss {
+    pub name: String,
+    pub spec: BossSpec,
+    /// Number of phases started so far.
+    pub phase: usize,
+}
+
+impl BossPhase {
+    /// Applies the phase to the `specs` of the skills used so far.
+    fn apply_to(&self, specs: &mut HashMap<Skill, SkillSpec>) {
+        for skill in &self.remove {
+            specs.remove(skill);
+        }
+        for (skill, spec) in &self.skills {
+            specs.entry(*skill).or_default().extend(spec.clone());
+        }
+    }
+}
+
+impl Boss {
+    /// The NPC's `skills` with the phases started so far, the later phases removing or
+    /// overriding the earlier specs.
+    pub fn with_started_phases(
+        &self,
+        skills: &HashMap<Skill, SkillSpec>,
+    ) -> HashMap<Skill, SkillSpec> {
+        let mut specs = skills.clone();
+        for phase in self.spec.phases.iter().take(self.phase) {
+            phase.apply_to(&mut specs);
+        }
+        specs
+    }
+}
+
+fn update_boss_phase(
+    mut q_boss: Query<(Entity, &mut Boss, &Health, &MaxHealth)>,
+    mut q_specs: Query<(Option<&mut SkillSpecs>, Option<&mut RemovedSkills>)>,
+    mut cmd: Commands,
+) {
+    for (entity, mut boss, health, max_health) in &mut q_boss {
+        let fraction = health.0 / max_health.max_hp as f32;
+        let mut new_specs = HashMap::new();
+        let mut new_removed = HashSet::new();
+        while let Some(phase) = boss.spec.phases.get(boss.phase) {
+            if fraction > phase.health {
+                break;
+            }
+            info!("Boss {} entered phase {}.", boss.name, boss.phase + 1);
+            new_removed.extend(phase.remove.iter().copied());
+            phase.apply_to(&mut new_specs);
+            boss.phase += 1;
+        }
+        if new_specs.is_empty() && new_removed.is_empty() {
+            continue;
+        }
+        let Ok((specs, removed)) = q_specs.get_mut(entity) else {
+            continue;
+        };
+        if !new_removed.is_empty() {
+            if let Some(mut removed) = removed {
+                removed.0.extend(new_removed.iter().copied());
+            } else {
+                cmd.entity(entity)
+                    .insert(RemovedSkills(new_removed.clone()));
+            }
+        }
+        let mut inserted = SkillSpecs::default();
+        let specs = specs.map_or(&mut inserted, Mut::into_inner);
+        // specs still waiting for the removed skills are dropped with them
+        specs.0.retain(|skill, _| !new_removed.contains(skill));
+        for (skill, spec) in new_specs {
+            specs
+                .0
+                .entry(skill)
+                .or_insert_with(|| (Level::default(), SkillSpec::default()))
+                .1
+                .extend(spec);
+        }
+        if !inserted.0.is_empty() {
+            cmd.entity(entity).insert(inserted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skills::{Attribute, Value};
+
+    fn spec(attr: &str, value: f32) -> SkillSpec {
+        [(Attribute::new(attr), Value::F(value))]
+            .into_iter()
+            .collect()
+    }
+
+    fn boss(phases: Vec<BossPhase>, phase: usize) -> Boss {
+        Boss {
+            name: "Boss".to_string(),
+            spec: BossSpec { phases },
+            phase,
+        }
+    }
+
+    #[test]
+    fn phases_merge_remove_and_replace_skills() {
+        let (swarm, laser, nova) = (Skill::new("Swarm"), Skill::new("Laser"), Skill::new("Nova"));
+        let skills = [(swarm, spec("Speed", 1.)), (laser, spec("Dps", 2.))]
+            .into_iter()
+            .collect();
+        let phases = vec![
+            BossPhase {
+                health: 0.5,
+                remove: vec![],
+                skills: [(swarm, spec("Range", 3.))].into_iter().collect(),
+            },
+            BossPhase {
+                health: 0.2,
+                remove: vec![laser, swarm],
+                skills: [(swarm, spec("Speed", 4.)), (nova, spec("Radius", 5.))]
+                    .into_iter()
+                    .collect(),
+            },
+        ];
+
+        let specs = boss(phases.clone(), 0).with_started_phases(&skills);
+        assert_eq!(specs, skills);
+
+        let specs = boss(phases.clone(), 1).with_started_phases(&skills);
+        let mut merged = spec("Speed", 1.);
+        merged.extend(spec("Range", 3.));
+        assert_eq!(specs[&swarm], merged);
+        assert_eq!(specs[&laser], spec("Dps", 2.));
+
+        let specs = boss(phases, 2).with_started_phases(&skills);
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[&swarm], spec("Speed", 4.));
+        assert_eq!(specs[&nova], spec("Radius", 5.));
+    }
+}
@@ -0,0 +1,170 @@
+use avian3d::prelude::*;
+use bevy::{platform::collections::HashMap, prelude::*};
+
+use crate::{
+    app::AppState,
+    player::{Player, PlayerControl, PlayerIntent},
+    skills::{Level, SkillUpgradeOptions, Skills, Upgrade, UpgradeChoice, swarm::Swarm},
+};
+
+/// Plays the game in place of the keyboard, see [`PlayerControl::Bot`].
+pub trait BotPolicy: Send + Sync + 'static {
+    /// Movement direction for the next fixed tick, same as [`PlayerIntent::movement`].
+    fn movement(&mut self, world: &mut World) -> Vec2;
+
+    /// Index into [`SkillUpgradeOptions::offers`] of the upgrade to take.
+    fn choose_upgrade(&mut self, world: &mut World) -> Option<usize>;
+}
+
+pub(crate) fn drive_bot(world: &mut World) {
+    world.resource_scope(|world, mut control: Mut<PlayerControl>| {
+        let PlayerControl::Bot(bot) = &mut *control else {
+            return;
+        };
+        match *world.resource::<State<AppState>>().get() {
+            AppState::Run => {
+                let movement = bot.movement(world);
+                world.resource_mut::<PlayerIntent>().movement = movement;
+            }
+            AppState::Upgrade if world.resource::<PlayerIntent>().upgrade.is_none() => {
+                let choice = bot.choose_upgrade(world).map(UpgradeChoice::Take);
+                world.resource_mut::<PlayerIntent>().upgrade = choice;
+            }
+            _ => {}
+        }
+    });
+}
+
+/// Runs away from the densest cluster of swarming NPCs, and picks the upgrade that adds
+/// the most DPS, or the best passive item when no skill is offered.
+#[derive(Default)]
+pub struct KitingBot;
+
+/// Side of the square cells used for finding NPC clusters.
+const CLUSTER_CELL: f32 = 10.;
+/// NPCs further away than this are ignored.
+const FLEE_RANGE: f32 = 40.;
+/// Fleeing is bent by this angle, so the bot circles around the swarm instead of
+/// running in a straight line.
+const KITE_ANGLE: f32 = std::f32::consts::PI / 6.;
+/// Past this distance from the origin the bot heads back.
+const ARENA_RADIUS: f32 = 400.;
+
+impl BotPolicy for KitingBot {
+    fn movement(&mut self, world: &mut World) -> Vec2 {
+        let Ok(player_pos) = world
+            .query_filtered::<&Position, With<Player>>()
+            .single(world)
+            .map(|pos| Vec2::new(pos.x, pos.z))
+        else {
+            return Vec2::ZERO;
+        };
+
+        let mut cells: HashMap<IVec2, (u32, Vec2)> = HashMap::new();
+        for pos in world.query_filtered::<&Position, With<Swarm>>().iter(world) {
+            let pos = Vec2::new(pos.x, pos.z);
+            if pos.distance(player_pos) > FLEE_RANGE {
+                continue;
+            }
+            let cell = cells
+                .entry((pos / CLUSTER_CELL).floor().as_ivec2())
+                .or_default();
+            cell.0 += 1;
+            cell.1 += pos;
+        }
+
+        let mut dir =
+            cells
+                .values()
+                .max_by_key(|(count, _)| *count)
+                .map_or(Vec2::ZERO, |(count, sum)| {
+                    let center = *sum / *count as f32;
+                    Vec2::from_angle(KITE_ANGLE).rotate((player_pos - center).normalize_or_zero())
+                });
+        if player_pos.length() > ARENA_RADIUS {
+            dir -= player_pos.normalize();
+        }
+        dir.normalize_or_zero()
+    }
+
+    fn choose_upgrade(&mut self, world: &mut World) -> Option<usize> {
+        best_upgrade(
+            world.resource::<Skills>(),
+            &world.resource::<SkillUpgradeOptions>().offers,
+        )
+    }
+}
+
+/// Index of the skill upgrade in `offers` that adds the most DPS or, when only passive
+/// items are offered, of the passive item that adds the most to its attributes. Always
+/// picks one of the `offers`, so the level up never waits on the bot.
+fn best_upgrade(skills: &Skills, offers: &[(Upgrade, Level)]) -> Option<usize> {
+    let mut best: Option<(usize, bool, f32)> = None;
+    for (idx, (upgrade, level)) in offers.iter().enumerate() {
+        let gain = match upgrade {
+            Upgrade::Skill(skill) => {
+                let dps = skills.field_at_level(*skill, *level, "dps");
+                let prev_dps = level
+                    .prev()
+                    .map_or(0., |prev| skills.field_at_level(*skill, prev, "dps"));
+                dps - prev_dps
+            }
+            Upgrade::Passive(_) => {
+                // passive levels hold total values, each counted by what it adds to 1
+                let total = |level| {
+                    skills.upgrade_spec(*upgrade, level).map_or(0., |spec| {
+                        spec.values()
+                            .map(|val| val.apply_to_f32(1.) - 1.)
+                            .sum::<f32>()
+                    })
+                };
+                total(*level) - level.prev().map_or(0., total)
+            }
+        };
+        let is_skill = matches!(upgrade, Upgrade::Skill(_));
+        if best.is_none_or(|(_, best_is_skill, best_gain)| {
+            (is_skill, gain) > (best_is_skill, best_gain)
+        }) {
+            best = Some((idx, is_skill, gain));
+        }
+    }
+    best.map(|(idx, ..)| idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skills::{Attribute, Passive, Skill, SkillSpec, Value, passive::PassiveMeta};
+
+    fn passive(levels: &[f32]) -> PassiveMeta {
+        PassiveMeta {
+            ui_name: String::new(),
+            levels: levels
+                .iter()
+                .map(|perc| SkillSpec::from_iter([(Attribute::new("Range"), Value::Perc(*perc))]))
+                .collect(),
+            rarities: vec![],
+        }
+    }
+
+    #[test]
+    fn takes_a_passive_when_no_skill_is_offered() {
+        let (armor, wings) = (Passive::new("Armor"), Passive::new("Wings"));
+        let mut skills = Skills::default();
+        skills.passives.insert(armor, passive(&[10.]));
+        skills.passives.insert(wings, passive(&[5., 30.]));
+        let second = Level::default().next(2).unwrap();
+        let offers = [
+            (Upgrade::Passive(armor), Level::default()),
+            (Upgrade::Passive(wings), second),
+        ];
+        assert_eq!(best_upgrade(&skills, &offers), Some(1));
+
+        let offers = [
+            (Upgrade::Passive(wings), second),
+            (Upgrade::Skill(Skill::new("Laser")), Level::default()),
+        ];
+        assert_eq!(best_upgrade(&skills, &offers), Some(1));
+        assert_eq!(best_upgrade(&skills, &[]), None);
+    }
+}
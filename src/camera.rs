@@ -111,7 +111,7 @@ fn main_camera(
         if delta.length_squared() > f32::EPSILON {
             any = true;
             camera_tr.rotation = yaw * camera_tr.rotation;
-            camera_tr.rotation = camera_tr.rotation * pitch;
+            camera_tr.rotation *= pitch;
         } else if scroll.abs() > 0.0 {
             any = true;
             main_camera.radius -= scroll * main_camera.radius * 0.2;
@@ -299,21 +299,18 @@ fn process_debug_commands(
     }
 }
 
-fn append_chars(buffer: &String, mut ev_char: EventReader<'_, '_, KeyboardInput>) -> String {
-    let mut new_buffer = buffer.clone();
+fn append_chars(buffer: &str, mut ev_char: EventReader<'_, '_, KeyboardInput>) -> String {
+    let mut new_buffer = buffer.to_owned();
     for ev in ev_char.read() {
         if !ev.state.is_pressed() {
             continue;
         }
-        match &ev.logical_key {
-            Key::Character(char) => {
-                for char in char.chars() {
-                    if !char.is_control() {
-                        new_buffer.push(char);
-                    }
+        if let Key::Character(char) = &ev.logical_key {
+            for char in char.chars() {
+                if !char.is_control() {
+                    new_buffer.push(char);
                 }
             }
-            _ => {}
         }
     }
     new_buffer
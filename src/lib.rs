@@ -1,11 +1,17 @@
 pub mod app;
+pub mod boss;
+pub mod bot;
 pub mod camera;
 pub mod debug_ui;
 pub mod light;
 pub mod npc;
 pub mod physics;
 pub mod player;
+pub mod replay;
+pub mod save;
 pub mod skills;
+pub mod spawn;
 pub mod terrain;
 pub mod ui;
 pub mod vfx;
+pub mod waves;
@@ -1,6 +1,9 @@
+use std::{fmt::Display, time::Duration};
+
 use avian3d::prelude::*;
 use bevy::{
     diagnostic::FrameTimeDiagnosticsPlugin,
+    log::LogPlugin,
     prelude::*,
     render::{
         RenderPlugin,
@@ -10,20 +13,93 @@ use bevy::{
 use bevy_hanabi::prelude::*;
 
 use swarm_hole::{
-    app::{AppState, InGame, MainMenuPlugin},
+    app::{GameStatePlugin, MainMenuPlugin, RunSeed, build_headless_app, run_headless},
+    boss::BossPlugin,
+    bot::KitingBot,
     camera::MainCameraPlugin,
     debug_ui::DebugUiPlugin,
     light::MainLightsPlugin,
     npc::NpcPlugin,
     physics::MainPhysicsPlugin,
-    player::PlayerPlugin,
+    player::{PlayerControl, PlayerPlugin, SelectedCharacter},
+    replay::{Replay, ReplayPlayback, ReplayPlugin, ReplayRecorder},
+    save::{SavePlugin, SaveSettings},
     skills::SkillPluginGroup,
     terrain::TerrainPlugin,
     ui::{INFINITE_TEMP_COLOR, MainUiPlugin},
     vfx::VfxPlugin,
+    waves::WavesPlugin,
 };
 
+/// Value following `name` on the command line.
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
+fn has_arg(name: &str) -> bool {
+    std::env::args().any(|arg| arg == name)
+}
+
+/// Value following `name` on the command line, read with `parse`. Exits when it is
+/// invalid, so it is only called once logging is set up.
+fn parse_arg<T, E: Display>(name: &str, parse: impl FnOnce(&str) -> Result<T, E>) -> Option<T> {
+    let value = arg_value(name)?;
+    match parse(&value) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            error!("Invalid {name} {value}: {err}");
+            std::process::exit(2);
+        }
+    }
+}
+
 fn main() {
+    let headless = has_arg("--headless");
+    let mut app = if headless {
+        let mut app = build_headless_app(Duration::from_secs_f64(1. / 64.));
+        app.add_plugins(LogPlugin::default());
+        app
+    } else {
+        build_window_app()
+    };
+
+    let seed = parse_arg("--seed", |seed| seed.parse().map(RunSeed));
+    let replay = parse_arg("--replay", |path| Replay::load(path));
+    let character = parse_arg("--character", |idx| idx.parse().map(SelectedCharacter));
+    if let Some(seed) = replay.as_ref().map(|replay| RunSeed(replay.seed)).or(seed) {
+        app.insert_resource(seed);
+    }
+    if let Some(character) = character {
+        app.insert_resource(character);
+    }
+    if let Some(path) = arg_value("--save") {
+        app.insert_resource(SaveSettings {
+            path: Some(path.into()),
+        });
+    }
+    if let Some(path) = arg_value("--record") {
+        app.world_mut().resource_mut::<ReplayRecorder>().path = Some(path.into());
+    }
+    if let Some(replay) = replay {
+        app.insert_resource(PlayerControl::Replay(ReplayPlayback::new(replay)));
+    } else if has_arg("--bot") {
+        app.insert_resource(PlayerControl::Bot(Box::new(KitingBot)));
+    }
+
+    if headless {
+        match run_headless(&mut app, Duration::from_secs(30 * 60)) {
+            Ok(state) => info!("Headless run ended in state {state:?}."),
+            Err(err) => {
+                error!("Headless run failed: {err}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        app.run();
+    }
+}
+
+fn build_window_app() -> App {
     let mut wgpu_settings = WgpuSettings {
         backends: Some(Backends::VULKAN),
         ..Default::default()
@@ -31,8 +107,8 @@ fn main() {
     wgpu_settings
         .features
         .set(WgpuFeatures::VERTEX_WRITABLE_STORAGE, true);
-    App::new()
-        .insert_resource(ClearColor(INFINITE_TEMP_COLOR))
+    let mut app = App::new();
+    app.insert_resource(ClearColor(INFINITE_TEMP_COLOR))
         .add_plugins((
             DefaultPlugins
                 .set(WindowPlugin {
@@ -54,11 +130,8 @@ fn main() {
             PhysicsDebugPlugin::default(),
             HanabiPlugin,
         ))
-        .init_state::<AppState>()
-        .add_computed_state::<InGame>()
-        .enable_state_scoped_entities::<AppState>()
-        .enable_state_scoped_entities::<InGame>()
         .add_plugins((
+            GameStatePlugin,
             MainMenuPlugin,
             MainPhysicsPlugin,
             MainCameraPlugin,
@@ -67,9 +140,13 @@ fn main() {
             TerrainPlugin,
             PlayerPlugin,
             NpcPlugin,
+            BossPlugin,
+            WavesPlugin,
             SkillPluginGroup,
             VfxPlugin,
             DebugUiPlugin,
+            ReplayPlugin,
         ))
-        .run();
+        .add_plugins(SavePlugin);
+    app
 }
@@ -2,19 +2,27 @@ use std::f32::consts::PI;
 
 use avian3d::prelude::*;
 use bevy::{
-    asset::{AssetLoader, LoadContext, io::Reader},
+    asset::{AssetLoader, LoadContext, LoadDirectError, io::Reader},
+    ecs::system::{SystemParam, SystemState},
     math::prelude::*,
     platform::collections::HashMap,
     prelude::*,
 };
-use rand::{distributions::WeightedIndex, prelude::*};
+use rand::distributions::{WeightedError, WeightedIndex};
 use serde::Deserialize;
 use thiserror::Error;
 
 use crate::{
-    app::{AppState, InGame, RunState},
+    app::{Headless, InGame, RunRng, RunState},
+    boss::{Boss, BossSpec},
     physics::Layer,
-    skills::{EquippedSkills, Level, Skill, SkillSpec, SkillSpecs},
+    replay::AssetHash,
+    skills::{
+        EquippedSkills, Level, SKILLS_ASSET_PATH, Skill, SkillRegistry, SkillSpec, SkillSpecError,
+        SkillSpecs, SkillsAsset,
+        pickup::{PickupDrop, PickupKind},
+    },
+    spawn::{Formation, SpawnPlacement},
 };
 
 pub struct NpcPlugin;
@@ -25,37 +33,57 @@ impl Plugin for NpcPlugin {
             .init_asset::<NonPlayerCharactersAsset>()
             .init_asset_loader::<NonPlayerCharactersAssetLoader>()
             .init_resource::<NpcHandles>()
-            .add_systems(Startup, setup_npc_handles)
             .add_systems(
-                OnTransition {
-                    exited: AppState::Menu,
-                    entered: AppState::Run,
-                },
-                spawn_start_npcs,
+                Startup,
+                (
+                    setup_npc_config,
+                    setup_npc_meshes.run_if(not(resource_exists::<Headless>)),
+                ),
             )
             .add_systems(Update, hot_reload_npcs);
     }
 }
 
+pub const NPC_MESHES: usize = 3;
+pub const NPC_MATERIALS: usize = 3;
+
 #[derive(Resource, Default)]
 pub struct NpcHandles {
-    pub meshes: Vec<Handle<Mesh>>,
-    pub materials: Vec<Handle<StandardMaterial>>,
+    pub meshes: [Handle<Mesh>; NPC_MESHES],
+    pub materials: [Handle<StandardMaterial>; NPC_MATERIALS],
     pub config: Handle<NonPlayerCharactersAsset>,
 }
 
-fn setup_npc_handles(
+pub const NPCS_ASSET_PATH: &str = "all.npcs.ron";
+
+/// The loaded NPCs asset.
+#[derive(SystemParam)]
+pub struct NpcConfig<'w> {
+    handles: Res<'w, NpcHandles>,
+    assets: Res<'w, Assets<NonPlayerCharactersAsset>>,
+}
+
+impl NpcConfig<'_> {
+    pub fn get(&self) -> Option<&NonPlayerCharactersAsset> {
+        self.assets.get(&self.handles.config)
+    }
+}
+
+fn setup_npc_config(mut npc_handles: ResMut<NpcHandles>, asset_server: Res<AssetServer>) {
+    npc_handles.config = asset_server.load(NPCS_ASSET_PATH);
+}
+
+fn setup_npc_meshes(
     mut npc_handles: ResMut<NpcHandles>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    asset_server: Res<AssetServer>,
 ) {
-    npc_handles.config = asset_server.load("all.npcs.ron");
-    npc_handles.meshes = vec![
+    npc_handles.meshes = [
         meshes.add(Sphere::new(0.5).mesh().ico(5).unwrap()),
         meshes.add(Sphere::new(1.).mesh().ico(8).unwrap()),
+        meshes.add(Sphere::new(2.5).mesh().ico(12).unwrap()),
     ];
-    npc_handles.materials = vec![
+    npc_handles.materials = [
         materials.add(StandardMaterial {
             base_color: bevy::color::palettes::css::LIMEGREEN.into(),
             metallic: 0.8,
@@ -68,6 +96,12 @@ fn setup_npc_handles(
             perceptual_roughness: 0.3,
             ..default()
         }),
+        materials.add(StandardMaterial {
+            base_color: bevy::color::palettes::css::DARK_VIOLET.into(),
+            metallic: 0.8,
+            perceptual_roughness: 0.3,
+            ..default()
+        }),
     ];
 }
 
@@ -80,19 +114,112 @@ pub struct NonPlayerCharacter {
     pub mesh_idx: usize,
     pub material_idx: usize,
     pub skills: HashMap<Skill, SkillSpec>,
+    /// Pickups dropped on death besides the XP drop.
+    #[serde(default)]
+    pub drops: Vec<PickupDrop>,
+    #[serde(default)]
+    pub boss: Option<BossSpec>,
 }
 
 #[derive(Asset, TypePath, Debug, Deserialize)]
-pub struct NonPlayerCharactersAsset(pub Vec<NonPlayerCharacter>);
+pub struct NonPlayerCharactersAsset(pub Vec<NonPlayerCharacter>, #[serde(skip)] pub AssetHash);
 
 impl NonPlayerCharactersAsset {
     pub fn get_npc_by_index(&self, index: NpcAssetIndex) -> Option<&NonPlayerCharacter> {
         self.0.get(index.0)
     }
+
+    pub fn get_npc_by_name(&self, name: &str) -> Option<(NpcAssetIndex, &NonPlayerCharacter)> {
+        self.0
+            .iter()
+            .enumerate()
+            .find(|(_, npc)| npc.name == name)
+            .map(|(idx, npc)| (NpcAssetIndex(idx), npc))
+    }
+
+    fn validate(
+        &self,
+        skills: &SkillsAsset,
+        registry: &SkillRegistry,
+    ) -> Result<(), NonPlayerCharactersAssetLoaderError> {
+        WeightedIndex::new(self.0.iter().map(|npc| npc.frequency))?;
+        for npc in &self.0 {
+            if npc.mesh_idx >= NPC_MESHES {
+                return Err(NonPlayerCharactersAssetLoaderError::MeshOutOfRange {
+                    npc: npc.name.clone(),
+                    mesh_idx: npc.mesh_idx,
+                });
+            }
+            if npc.material_idx >= NPC_MATERIALS {
+                return Err(NonPlayerCharactersAssetLoaderError::MaterialOutOfRange {
+                    npc: npc.name.clone(),
+                    material_idx: npc.material_idx,
+                });
+            }
+            for (skill, spec) in &npc.skills {
+                skills
+                    .validate_spec(registry, *skill, spec)
+                    .map_err(|error| NonPlayerCharactersAssetLoaderError::InvalidSkill {
+                        npc: npc.name.clone(),
+                        error,
+                    })?;
+            }
+            for drop in &npc.drops {
+                if !(drop.chance > 0. && drop.chance <= 1. && drop.pickup.is_valid()) {
+                    return Err(NonPlayerCharactersAssetLoaderError::InvalidDrop {
+                        npc: npc.name.clone(),
+                        pickup: drop.pickup,
+                        chance: drop.chance,
+                    });
+                }
+            }
+            let Some(boss) = &npc.boss else {
+                continue;
+            };
+            let mut prev_health = 1.;
+            for (idx, phase) in boss.phases.iter().enumerate() {
+                if !(phase.health > 0. && phase.health < prev_health) {
+                    return Err(NonPlayerCharactersAssetLoaderError::InvalidBossPhase {
+                        npc: npc.name.clone(),
+                        phase: idx,
+                        health: phase.health,
+                    });
+                }
+                prev_health = phase.health;
+                for skill in &phase.remove {
+                    skills
+                        .validate_spec(registry, *skill, &SkillSpec::default())
+                        .map_err(|error| NonPlayerCharactersAssetLoaderError::InvalidSkill {
+                            npc: npc.name.clone(),
+                            error,
+                        })?;
+                }
+                for (skill, spec) in &phase.skills {
+                    skills
+                        .validate_spec(registry, *skill, spec)
+                        .map_err(|error| NonPlayerCharactersAssetLoaderError::InvalidSkill {
+                            npc: npc.name.clone(),
+                            error,
+                        })?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
-#[derive(Default)]
-pub struct NonPlayerCharactersAssetLoader;
+/// Loads `*.npcs.ron` files and validates them against the skills asset.
+pub struct NonPlayerCharactersAssetLoader {
+    registry: SkillRegistry,
+}
+
+impl FromWorld for NonPlayerCharactersAssetLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            registry: world.get_resource_or_init::<SkillRegistry>().clone(),
+        }
+    }
+}
 
 #[non_exhaustive]
 #[derive(Debug, Error)]
@@ -101,6 +228,34 @@ pub enum NonPlayerCharactersAssetLoaderError {
     Io(#[from] std::io::Error),
     #[error("Could not parse RON: {0}")]
     RonSpannedError(#[from] ron::error::SpannedError),
+    #[error("Could not load skills asset: {0}")]
+    SkillsAsset(#[from] Box<LoadDirectError>),
+    #[error("Invalid NPC frequencies: {0}")]
+    InvalidFrequencies(#[from] WeightedError),
+    #[error("NPC {npc:?} has mesh_idx {mesh_idx}, but there are only {NPC_MESHES} meshes")]
+    MeshOutOfRange { npc: String, mesh_idx: usize },
+    #[error(
+        "NPC {npc:?} has material_idx {material_idx}, but there are only {NPC_MATERIALS} materials"
+    )]
+    MaterialOutOfRange { npc: String, material_idx: usize },
+    #[error("Invalid skill for NPC {npc:?}: {error}")]
+    InvalidSkill { npc: String, error: SkillSpecError },
+    #[error(
+        "Boss phase {phase} of NPC {npc:?} starts at health {health}, which is not below the previous phase"
+    )]
+    InvalidBossPhase {
+        npc: String,
+        phase: usize,
+        health: f32,
+    },
+    #[error(
+        "NPC {npc:?} drops {pickup:?} with chance {chance}, expected a chance in (0, 1] and a non zero pickup"
+    )]
+    InvalidDrop {
+        npc: String,
+        pickup: PickupKind,
+        chance: f32,
+    },
 }
 
 impl AssetLoader for NonPlayerCharactersAssetLoader {
@@ -111,11 +266,19 @@ impl AssetLoader for NonPlayerCharactersAssetLoader {
         &self,
         reader: &mut dyn Reader,
         _settings: &Self::Settings,
-        _load_context: &mut LoadContext<'_>,
+        load_context: &mut LoadContext<'_>,
     ) -> Result<Self::Asset, Self::Error> {
         let mut bytes = Vec::new();
         reader.read_to_end(&mut bytes).await?;
-        let custom_asset = ron::de::from_bytes::<NonPlayerCharactersAsset>(&bytes)?;
+        let mut custom_asset = ron::de::from_bytes::<NonPlayerCharactersAsset>(&bytes)?;
+        custom_asset.1 = AssetHash::of(&bytes);
+        let skills = load_context
+            .loader()
+            .immediate()
+            .load::<SkillsAsset>(SKILLS_ASSET_PATH)
+            .await
+            .map_err(Box::new)?;
+        custom_asset.validate(skills.get(), &self.registry)?;
         Ok(custom_asset)
     }
 
@@ -126,7 +289,9 @@ impl AssetLoader for NonPlayerCharactersAssetLoader {
 
 #[derive(Component, Reflect, Clone)]
 pub struct Npc {
+    pub name: String,
     pub xp_drop: u32,
+    pub drops: Vec<PickupDrop>,
 }
 
 pub struct SpawnNpc {
@@ -151,7 +316,9 @@ impl Command for SpawnNpc {
             let id = world
                 .spawn((
                     Npc {
+                        name: npc.name.clone(),
                         xp_drop: npc.xp_drop,
+                        drops: npc.drops.clone(),
                     },
                     HotReloadNpc(self.npc_index),
                     Mesh3d(npc_handles.meshes.get(npc.mesh_idx).unwrap().clone()),
@@ -168,6 +335,13 @@ impl Command for SpawnNpc {
             world
                 .entity_mut(id)
                 .insert(Name::new(format!("NPC {:?} ({id:?})", npc.name)));
+            if let Some(boss) = &npc.boss {
+                world.entity_mut(id).insert(Boss {
+                    name: npc.name.clone(),
+                    spec: boss.clone(),
+                    phase: 0,
+                });
+            }
         }
 
         if let Some(mut run_state) = world.get_resource_mut::<RunState>() {
@@ -176,13 +350,8 @@ impl Command for SpawnNpc {
     }
 }
 
-fn spawn_start_npcs(mut cmd: Commands) {
-    cmd.queue(SpawnRandomNpcs {
-        count: 200,
-        distance: 10.,
-    });
-}
-
+/// Spawns `count` NPCs picked by their frequency in a disk just outside the visible
+/// ground, with about `distance` between them.
 pub struct SpawnRandomNpcs {
     pub count: usize,
     pub distance: f32,
@@ -191,42 +360,31 @@ pub struct SpawnRandomNpcs {
 impl Command for SpawnRandomNpcs {
     fn apply(self, world: &mut World) {
         info!("spawning {} NPCs...", self.count);
-        let npcs = {
-            let Some(npc_handles) = world.get_resource::<NpcHandles>() else {
-                error!("NPC handles resource not found!");
-                return;
-            };
-            let Some(npc_assets) = world.get_resource::<Assets<NonPlayerCharactersAsset>>() else {
-                error!("NPC assets not found!");
-                return;
-            };
-            let Some(npcs) = npc_assets.get(&npc_handles.config) else {
+        let mut state = SystemState::<(NpcConfig, SpawnPlacement, ResMut<RunRng>)>::new(world);
+        let spawns = {
+            let (npc_config, placement, mut rng) = state.get_mut(world);
+            let Some(npcs) = npc_config.get() else {
                 error!("NPC config asset not loaded!");
                 return;
             };
-            npcs.0.clone()
+            let candidates = npcs
+                .0
+                .iter()
+                .enumerate()
+                .filter(|(_, npc)| npc.boss.is_none())
+                .map(|(idx, npc)| (NpcAssetIndex(idx), npc))
+                .collect::<Vec<_>>();
+            let spread = (self.distance.powi(2) * self.count as f32 / PI).sqrt();
+            placement.place_group(
+                Formation::Cluster { margin: 0., spread },
+                &candidates,
+                self.count,
+                &mut vec![],
+                &mut rng.spawn,
+            )
         };
-
-        let unit_area = self.distance.powi(2);
-        let radius = (unit_area * self.count as f32 / PI).sqrt();
-        let circle = Circle::new(radius);
-        let samples = circle
-            .interior_dist()
-            .sample_iter(thread_rng())
-            .take(self.count)
-            .collect::<Vec<_>>();
-
-        let mut rng = thread_rng();
-        let npc_idx = WeightedIndex::new(npcs.iter().map(|npc| npc.frequency)).unwrap();
-        for pt in samples {
-            let idx = npc_idx.sample(&mut rng);
-            let npc = &npcs[idx];
-            SpawnNpc {
-                character: npc.clone(),
-                npc_index: NpcAssetIndex(idx),
-                location: pt,
-            }
-            .apply(world);
+        for spawn in spawns {
+            spawn.apply(world);
         }
     }
 }
@@ -241,22 +399,30 @@ fn hot_reload_npcs(
     npc_handles: Res<NpcHandles>,
     npcs_assets: Res<Assets<NonPlayerCharactersAsset>>,
     mut skills_asset_events: EventReader<AssetEvent<NonPlayerCharactersAsset>>,
-    mut q_npcs: Query<(Entity, &mut Npc, &HotReloadNpc)>,
+    mut q_npcs: Query<(Entity, &mut Npc, &HotReloadNpc, Option<&mut Boss>)>,
     mut cmd: Commands,
 ) {
     for ev in skills_asset_events.read() {
         let h = npc_handles.config.clone();
-        if ev.is_loaded_with_dependencies(&h) {
-            if let Some(asset) = npcs_assets.get(&h) {
-                for (entity, mut npc, hot_reload_npc) in &mut q_npcs {
-                    if let Some(npc_src) = asset.get_npc_by_index(hot_reload_npc.0) {
-                        npc.xp_drop = npc_src.xp_drop;
-                        let mut specs = SkillSpecs::default();
-                        for (skill, spec) in &npc_src.skills {
-                            specs.0.insert(*skill, (Level::default(), spec.clone()));
-                        }
-                        cmd.entity(entity).insert(specs);
+        if ev.is_loaded_with_dependencies(&h)
+            && let Some(asset) = npcs_assets.get(&h)
+        {
+            for (entity, mut npc, hot_reload_npc, boss) in &mut q_npcs {
+                if let Some(npc_src) = asset.get_npc_by_index(hot_reload_npc.0) {
+                    npc.name = npc_src.name.clone();
+                    npc.xp_drop = npc_src.xp_drop;
+                    npc.drops = npc_src.drops.clone();
+                    let skills = if let (Some(mut boss), Some(boss_src)) = (boss, &npc_src.boss) {
+                        boss.spec = boss_src.clone();
+                        boss.with_started_phases(&npc_src.skills)
+                    } else {
+                        npc_src.skills.clone()
+                    };
+                    let mut specs = SkillSpecs::default();
+                    for (skill, spec) in skills {
+                        specs.0.insert(skill, (Level::default(), spec));
                     }
+                    cmd.entity(entity).insert(specs);
                 }
             }
         }
@@ -18,8 +18,10 @@ impl Plugin for MainPhysicsPlugin {
     }
 }
 
-fn setup_physics(mut config_store: ResMut<GizmoConfigStore>) {
-    config_store.config_mut::<PhysicsGizmos>().0.enabled = false;
+fn setup_physics(config_store: Option<ResMut<GizmoConfigStore>>) {
+    if let Some(mut config_store) = config_store {
+        config_store.config_mut::<PhysicsGizmos>().0.enabled = false;
+    }
 }
 
 fn update_physics_paused(mut time: ResMut<Time<Physics>>, app_state: Res<State<AppState>>) {
@@ -51,6 +53,12 @@ pub enum Layer {
     NPC,
     Building,
     Ground,
+    Projectile,
+}
+
+/// Layer of the enemies of a player or NPC.
+pub fn enemy_layer(is_player: bool) -> Layer {
+    if is_player { Layer::NPC } else { Layer::Player }
 }
 
 fn kinematic_collision(
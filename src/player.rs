@@ -1,6 +1,6 @@
 use avian3d::{math::*, prelude::*};
 use bevy::{
-    asset::{AssetLoader, LoadContext, io::Reader},
+    asset::{AssetLoader, LoadContext, LoadDirectError, io::Reader},
     platform::collections::HashMap,
     prelude::*,
 };
@@ -8,13 +8,17 @@ use serde::Deserialize;
 use thiserror::Error;
 
 use crate::{
-    app::{AppState, InGame},
+    app::{AppState, Headless, InGame},
+    bot::{BotPolicy, drive_bot},
     camera::MainCameraFocusEvent,
     debug_ui::DebugUi,
     physics::Layer,
+    replay::{AssetHash, ReplayPlayback},
+    save::StartingBonuses,
     skills::{
-        EquippedSkills, HotReloadEquippedSkills, Level, MaxUpgradableSkills, Skill, SkillSpec,
-        SkillSpecs, Skills,
+        EquippedSkills, HotReloadEquippedSkills, Level, MaxUpgradableSkills, QueuedSkillSpecs,
+        SKILLS_ASSET_PATH, Skill, SkillRegistry, SkillSpec, SkillSpecError, SkillSpecs, Skills,
+        SkillsAsset, UpgradeChoice, apply_upgrade_selection, status::StatusEffects,
     },
 };
 
@@ -27,7 +31,17 @@ impl Plugin for PlayerPlugin {
             .init_asset::<PlayerCharactersAsset>()
             .init_asset_loader::<PlayerCharactersAssetLoader>()
             .init_resource::<PcHandles>()
-            .add_systems(Startup, setup_player_handles)
+            .init_resource::<SelectedCharacter>()
+            .init_resource::<PlayerIntent>()
+            .init_resource::<PlayerControl>()
+            .add_event::<MainCameraFocusEvent>()
+            .add_systems(
+                Startup,
+                (
+                    setup_player_config,
+                    setup_player_meshes.run_if(not(resource_exists::<Headless>)),
+                ),
+            )
             .add_systems(
                 OnTransition {
                     exited: AppState::Menu,
@@ -35,39 +49,65 @@ impl Plugin for PlayerPlugin {
                 },
                 spawn_main_player,
             )
-            .add_systems(FixedUpdate, move_player.run_if(in_state(AppState::Run)));
+            .add_systems(OnEnter(AppState::Run), clear_intent)
+            .add_systems(
+                Update,
+                keyboard_intent
+                    .run_if(keyboard_control)
+                    .run_if(in_state(AppState::Run)),
+            )
+            .add_systems(
+                Update,
+                // game time is paused while upgrading, so there are no fixed ticks
+                drive_bot
+                    .run_if(in_state(AppState::Upgrade))
+                    .run_if(bot_control)
+                    .before(apply_upgrade_selection),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    drive_bot
+                        .run_if(in_state(AppState::Run))
+                        .run_if(bot_control),
+                    move_player.run_if(in_state(AppState::Run)),
+                )
+                    .chain(),
+            );
     }
 }
 
+pub const PC_MESHES: usize = 1;
+pub const PC_MATERIALS: usize = 1;
+
 #[derive(Resource, Default)]
 pub struct PcHandles {
-    pub meshes: Vec<Handle<Mesh>>,
-    pub materials: Vec<Handle<StandardMaterial>>,
+    pub meshes: [Handle<Mesh>; PC_MESHES],
+    pub materials: [Handle<StandardMaterial>; PC_MATERIALS],
     pub config: Handle<PlayerCharactersAsset>,
 }
 
-fn setup_player_handles(
+fn setup_player_config(mut pc_handles: ResMut<PcHandles>, asset_server: Res<AssetServer>) {
+    pc_handles.config = asset_server.load("all.pcs.ron");
+}
+
+fn setup_player_meshes(
     mut pc_handles: ResMut<PcHandles>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    asset_server: Res<AssetServer>,
 ) {
-    pc_handles.config = asset_server.load("all.pcs.ron");
-
     let (height, width) = (2., 0.3);
     let cap_h = height - 2. * width;
-    pc_handles.meshes = vec![
-        meshes.add(
-            Capsule3d::new(width, cap_h)
-                .mesh()
-                .rings(0)
-                .latitudes(16)
-                .longitudes(32)
-                .uv_profile(bevy::render::mesh::CapsuleUvProfile::Aspect)
-                .build(),
-        ),
-    ];
-    pc_handles.materials = vec![materials.add(StandardMaterial {
+    pc_handles.meshes = [meshes.add(
+        Capsule3d::new(width, cap_h)
+            .mesh()
+            .rings(0)
+            .latitudes(16)
+            .longitudes(32)
+            .uv_profile(bevy::render::mesh::CapsuleUvProfile::Aspect)
+            .build(),
+    )];
+    pc_handles.materials = [materials.add(StandardMaterial {
         base_color: Color::BLACK,
         metallic: 0.0,
         perceptual_roughness: 0.5,
@@ -84,15 +124,91 @@ pub struct PlayerCharacter {
     pub mesh_idx: usize,
     pub material_idx: usize,
     pub max_selected_skills: u8,
+    /// Slots for passive items, separate from the selected skills.
+    #[serde(default)]
+    pub max_passives: u8,
     pub default_skills: HashMap<Skill, SkillSpec>,
     pub selected_skills: Vec<Skill>,
+    /// Currency needed to unlock the character, available from the start when 0.
+    #[serde(default)]
+    pub unlock_cost: u32,
 }
 
 #[derive(Asset, TypePath, Debug, Deserialize)]
-pub struct PlayerCharactersAsset(pub Vec<PlayerCharacter>);
+pub struct PlayerCharactersAsset(pub Vec<PlayerCharacter>, #[serde(skip)] pub AssetHash);
 
-#[derive(Default)]
-pub struct PlayerCharactersAssetLoader;
+impl PlayerCharactersAsset {
+    fn validate(
+        &self,
+        skills: &SkillsAsset,
+        registry: &SkillRegistry,
+    ) -> Result<(), PlayerCharactersAssetLoaderError> {
+        if self.0.is_empty() {
+            return Err(PlayerCharactersAssetLoaderError::NoCharacters);
+        }
+        for pc in &self.0 {
+            if pc.mesh_idx >= PC_MESHES {
+                return Err(PlayerCharactersAssetLoaderError::MeshOutOfRange {
+                    pc: pc.name.clone(),
+                    mesh_idx: pc.mesh_idx,
+                });
+            }
+            if pc.material_idx >= PC_MATERIALS {
+                return Err(PlayerCharactersAssetLoaderError::MaterialOutOfRange {
+                    pc: pc.name.clone(),
+                    material_idx: pc.material_idx,
+                });
+            }
+            for (skill, spec) in &pc.default_skills {
+                skills
+                    .validate_spec(registry, *skill, spec)
+                    .map_err(|error| PlayerCharactersAssetLoaderError::InvalidSkill {
+                        pc: pc.name.clone(),
+                        error,
+                    })?;
+            }
+            if pc.selected_skills.len() > pc.max_selected_skills as usize {
+                return Err(PlayerCharactersAssetLoaderError::TooManySelectedSkills {
+                    pc: pc.name.clone(),
+                    count: pc.selected_skills.len(),
+                    max: pc.max_selected_skills,
+                });
+            }
+            for skill in &pc.selected_skills {
+                if !skills.skills.contains_key(skill) {
+                    return Err(PlayerCharactersAssetLoaderError::UndeclaredSkill {
+                        pc: pc.name.clone(),
+                        skill: *skill,
+                    });
+                }
+                if skills
+                    .upgrades
+                    .get(skill)
+                    .is_none_or(|levels| levels.is_empty())
+                {
+                    return Err(PlayerCharactersAssetLoaderError::NoUpgrades {
+                        pc: pc.name.clone(),
+                        skill: *skill,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Loads `*.pcs.ron` files and validates them against the skills asset.
+pub struct PlayerCharactersAssetLoader {
+    registry: SkillRegistry,
+}
+
+impl FromWorld for PlayerCharactersAssetLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            registry: world.get_resource_or_init::<SkillRegistry>().clone(),
+        }
+    }
+}
 
 #[non_exhaustive]
 #[derive(Debug, Error)]
@@ -101,6 +217,24 @@ pub enum PlayerCharactersAssetLoaderError {
     Io(#[from] std::io::Error),
     #[error("Could not parse RON: {0}")]
     RonSpannedError(#[from] ron::error::SpannedError),
+    #[error("Could not load skills asset: {0}")]
+    SkillsAsset(#[from] Box<LoadDirectError>),
+    #[error("No player characters defined")]
+    NoCharacters,
+    #[error("PC {pc:?} has mesh_idx {mesh_idx}, but there are only {PC_MESHES} meshes")]
+    MeshOutOfRange { pc: String, mesh_idx: usize },
+    #[error(
+        "PC {pc:?} has material_idx {material_idx}, but there are only {PC_MATERIALS} materials"
+    )]
+    MaterialOutOfRange { pc: String, material_idx: usize },
+    #[error("Invalid default skill for PC {pc:?}: {error}")]
+    InvalidSkill { pc: String, error: SkillSpecError },
+    #[error("Selected skill {skill} of PC {pc:?} has no upgrades")]
+    NoUpgrades { pc: String, skill: Skill },
+    #[error("Selected skill {skill} of PC {pc:?} is not declared in the skills asset")]
+    UndeclaredSkill { pc: String, skill: Skill },
+    #[error("PC {pc:?} has {count} selected skills, but max_selected_skills is {max}")]
+    TooManySelectedSkills { pc: String, count: usize, max: u8 },
 }
 
 impl AssetLoader for PlayerCharactersAssetLoader {
@@ -111,11 +245,19 @@ impl AssetLoader for PlayerCharactersAssetLoader {
         &self,
         reader: &mut dyn Reader,
         _settings: &Self::Settings,
-        _load_context: &mut LoadContext<'_>,
+        load_context: &mut LoadContext<'_>,
     ) -> Result<Self::Asset, Self::Error> {
         let mut bytes = Vec::new();
         reader.read_to_end(&mut bytes).await?;
-        let custom_asset = ron::de::from_bytes::<PlayerCharactersAsset>(&bytes)?;
+        let mut custom_asset = ron::de::from_bytes::<PlayerCharactersAsset>(&bytes)?;
+        custom_asset.1 = AssetHash::of(&bytes);
+        let skills = load_context
+            .loader()
+            .immediate()
+            .load::<SkillsAsset>(SKILLS_ASSET_PATH)
+            .await
+            .map_err(Box::new)?;
+        custom_asset.validate(skills.get(), &self.registry)?;
         Ok(custom_asset)
     }
 
@@ -124,18 +266,33 @@ impl AssetLoader for PlayerCharactersAssetLoader {
     }
 }
 
-fn spawn_main_player(
+/// Index into [`PlayerCharactersAsset`] of the character picked in the main menu.
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct SelectedCharacter(pub usize);
+
+pub(crate) fn spawn_main_player(
     pc_handles: Res<PcHandles>,
     pc_assets: Res<Assets<PlayerCharactersAsset>>,
+    selected: Res<SelectedCharacter>,
+    skills: Res<Skills>,
+    bonuses: Res<StartingBonuses>,
     mut cmd: Commands,
 ) {
     let Some(pcs) = pc_assets.get(&pc_handles.config) else {
         error!("PC config asset not loaded!");
         return;
     };
+    let character = pcs.0.get(selected.0).unwrap_or_else(|| {
+        warn!(
+            "Selected character {} not found, spawning {}.",
+            selected.0, pcs.0[0].name
+        );
+        &pcs.0[0]
+    });
     cmd.queue(SpawnPlayer {
-        character: pcs.0.get(0).unwrap().clone(),
+        character: character.clone(),
         location: Vec2::ZERO,
+        bonuses: bonuses.specs(&skills),
     });
 }
 
@@ -147,6 +304,8 @@ pub struct Player {
 pub struct SpawnPlayer {
     pub character: PlayerCharacter,
     pub location: Vec2,
+    /// Specs applied after the starting ones, in order.
+    pub bonuses: Vec<(Skill, SkillSpec)>,
 }
 
 impl Command for SpawnPlayer {
@@ -171,6 +330,12 @@ impl Command for SpawnPlayer {
                     .insert(*skill, (Level::default(), levels[0].clone()));
             }
         }
+        let mut queued = QueuedSkillSpecs::default();
+        for (skill, spec) in self.bonuses {
+            if specs.0.contains_key(&skill) {
+                queued.push(skill, Level::default(), spec);
+            }
+        }
 
         world.spawn((
             Name::new(format!("Player {}", pc.name)),
@@ -189,58 +354,112 @@ impl Command for SpawnPlayer {
             )
             .with_max_distance(0.11)
             .with_max_hits(1),
-            MaxUpgradableSkills(pc.max_selected_skills),
+            MaxUpgradableSkills {
+                skills: pc.max_selected_skills,
+                passives: pc.max_passives,
+            },
             EquippedSkills::new(&pc.selected_skills),
             specs,
+            queued,
             HotReloadEquippedSkills,
             StateScoped(InGame),
         ));
     }
 }
 
-const PLAYER_ACC_STEPS: f32 = 10.;
+// player input
+
+/// What the player wants to do, produced by keyboard input or by a [`BotPolicy`].
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq)]
+pub struct PlayerIntent {
+    /// Movement direction on the ground plane, with `y` mapped to world `z`.
+    pub movement: Vec2,
+    pub jump: bool,
+    /// What to do with the upgrades offered on level up.
+    pub upgrade: Option<UpgradeChoice>,
+}
+
+/// Source of the [`PlayerIntent`].
+#[derive(Resource, Default)]
+pub enum PlayerControl {
+    #[default]
+    Keyboard,
+    Bot(Box<dyn BotPolicy>),
+    Replay(ReplayPlayback),
+}
+
+fn keyboard_control(control: Res<PlayerControl>) -> bool {
+    matches!(*control, PlayerControl::Keyboard)
+}
 
-fn move_player(
+fn bot_control(control: Res<PlayerControl>) -> bool {
+    matches!(*control, PlayerControl::Bot(_))
+}
+
+/// Drops the intent left over from before the run started or resumed, like keys
+/// pressed in the menus.
+fn clear_intent(mut intent: ResMut<PlayerIntent>) {
+    *intent = PlayerIntent::default();
+}
+
+fn keyboard_intent(
     keyboard: Res<ButtonInput<KeyCode>>,
     debug_ui: Res<DebugUi>,
-    mut q_player: Query<(&Transform, &Player, &mut LinearVelocity, &ShapeHits)>,
+    mut intent: ResMut<PlayerIntent>,
+) {
+    intent.movement = Vec2::ZERO;
+    if debug_ui.has_focus() {
+        return;
+    }
+    if keyboard.pressed(KeyCode::KeyW) || keyboard.pressed(KeyCode::ArrowUp) {
+        intent.movement.y -= 1.;
+    }
+    if keyboard.pressed(KeyCode::KeyA) || keyboard.pressed(KeyCode::ArrowLeft) {
+        intent.movement.x -= 1.;
+    }
+    if keyboard.pressed(KeyCode::KeyS) || keyboard.pressed(KeyCode::ArrowDown) {
+        intent.movement.y += 1.;
+    }
+    if keyboard.pressed(KeyCode::KeyD) || keyboard.pressed(KeyCode::ArrowRight) {
+        intent.movement.x += 1.;
+    }
+    if keyboard.just_pressed(KeyCode::Space) {
+        intent.jump = true;
+    }
+}
+
+const PLAYER_ACC_STEPS: f32 = 10.;
+
+pub(crate) fn move_player(
+    mut intent: ResMut<PlayerIntent>,
+    mut q_player: Query<(
+        &Transform,
+        &Player,
+        &mut LinearVelocity,
+        &ShapeHits,
+        Option<&StatusEffects>,
+    )>,
     mut ev_refocus: EventWriter<MainCameraFocusEvent>,
 ) {
-    for (player_tr, player, mut linear_velocity, ground_hits) in &mut q_player {
+    for (player_tr, player, mut linear_velocity, ground_hits, statuses) in &mut q_player {
         if !ground_hits.is_empty() {
             linear_velocity.y = 0.0;
         } else {
             linear_velocity.y -= 0.4;
         }
 
-        let acc = player.speed / PLAYER_ACC_STEPS;
+        let speed = player.speed * statuses.map_or(1., StatusEffects::speed_multiplier);
+        let acc = speed / PLAYER_ACC_STEPS;
         let mut vel = Vec2::new(linear_velocity.x, linear_velocity.z);
-        if !debug_ui.has_focus() {
-            let mut changed = false;
-            if keyboard.pressed(KeyCode::KeyW) || keyboard.pressed(KeyCode::ArrowUp) {
-                vel.y -= acc;
-                changed = true;
-            }
-            if keyboard.pressed(KeyCode::KeyA) || keyboard.pressed(KeyCode::ArrowLeft) {
-                vel.x -= acc;
-                changed = true;
-            }
-            if keyboard.pressed(KeyCode::KeyS) || keyboard.pressed(KeyCode::ArrowDown) {
-                vel.y += acc;
-                changed = true;
-            }
-            if keyboard.pressed(KeyCode::KeyD) || keyboard.pressed(KeyCode::ArrowRight) {
-                vel.x += acc;
-                changed = true;
-            }
-            if !changed {
-                vel *= 0.8;
-            }
-            if keyboard.just_pressed(KeyCode::Space) && !ground_hits.is_empty() {
-                linear_velocity.y += 20.0;
-            }
+        if intent.movement != Vec2::ZERO {
+            vel += intent.movement * acc;
+        } else {
+            vel *= 0.8;
+        }
+        if intent.jump && !ground_hits.is_empty() {
+            linear_velocity.y += 20.0;
         }
-        vel = vel.clamp_length_max(player.speed);
+        vel = vel.clamp_length_max(speed);
 
         linear_velocity.x = vel.x;
         linear_velocity.z = vel.y;
@@ -250,4 +469,5 @@ fn move_player(
             focus: player_tr.translation,
         });
     }
+    intent.jump = false;
 }
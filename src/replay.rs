@@ -0,0 +1,525 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    time::{TimeSystem, TimeUpdateStrategy},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    app::{AppState, RunSeed, frame_in_run},
+    bot::drive_bot,
+    npc::NpcConfig,
+    player::{
+        PcHandles, PlayerCharactersAsset, PlayerControl, PlayerIntent, SelectedCharacter,
+        move_player, spawn_main_player,
+    },
+    save::StartingBonuses,
+    skills::{SkillUpgradeOptions, Skills, SkillsAsset, UpgradeChoice, apply_upgrade_selection},
+    ui::{update_skill_upgrade_ui, update_upgrade_action_ui},
+    waves::{SpawnDirector, WavesAsset},
+};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayRecorder>()
+            .add_systems(
+                OnTransition {
+                    exited: AppState::Menu,
+                    entered: AppState::Run,
+                },
+                (start_recording, start_playback.run_if(replay_control))
+                    .chain()
+                    .before(spawn_main_player),
+            )
+            .add_systems(OnEnter(AppState::Menu), rewind_playback)
+            .add_systems(OnExit(AppState::Run), restore_frame_time)
+            .add_systems(
+                First,
+                play_frame_time
+                    .run_if(replay_control)
+                    .run_if(frame_in_run)
+                    .before(TimeSystem),
+            )
+            .add_systems(
+                Update,
+                record_frame_time
+                    .run_if(not(replay_control))
+                    .run_if(in_state(AppState::Run)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    play_input.run_if(replay_control),
+                    record_input.run_if(not(replay_control)),
+                )
+                    .after(drive_bot)
+                    .before(move_player)
+                    .run_if(in_state(AppState::Run)),
+            )
+            .add_systems(
+                Update,
+                (
+                    play_upgrade.run_if(replay_control),
+                    // after every system that may pick the upgrade
+                    record_upgrade
+                        .run_if(not(replay_control))
+                        .after(drive_bot)
+                        .after(update_skill_upgrade_ui)
+                        .after(update_upgrade_action_ui),
+                )
+                    .before(apply_upgrade_selection)
+                    .run_if(in_state(AppState::Upgrade)),
+            )
+            .add_systems(OnEnter(AppState::Lost), save_replay)
+            .add_systems(OnEnter(AppState::Won), save_replay)
+            .add_systems(
+                OnTransition {
+                    exited: AppState::Paused,
+                    entered: AppState::Menu,
+                },
+                save_replay,
+            );
+    }
+}
+
+pub const DEFAULT_REPLAY_PATH: &str = "last_run.replay.ron";
+
+/// FNV-1a hash of an asset file, stable across platforms and compiler versions.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug, Serialize, Deserialize)]
+pub struct AssetHash(pub u64);
+
+impl AssetHash {
+    pub fn of(bytes: &[u8]) -> Self {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        Self(hash)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug, Serialize, Deserialize)]
+pub struct AssetHashes {
+    pub skills: AssetHash,
+    pub npcs: AssetHash,
+    pub pcs: AssetHash,
+    pub waves: AssetHash,
+}
+
+/// Player input of one fixed tick, see [`PlayerIntent`].
+#[derive(Copy, Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+pub struct InputFrame {
+    pub movement: [f32; 2],
+    pub jump: bool,
+}
+
+/// Everything needed to replay a run: its seed, the assets it was played with and the
+/// player input.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    /// Index of the [`SelectedCharacter`].
+    #[serde(default)]
+    pub character: usize,
+    /// Levels of the [`StartingBonuses`] bought when the run started.
+    #[serde(default)]
+    pub bonuses: BTreeMap<String, u32>,
+    pub assets: AssetHashes,
+    /// Durations in nanoseconds of the frames spent in [`AppState::Run`], run length
+    /// encoded. The game time advances by them on playback, so the frames and fixed
+    /// ticks are the same as when recording, whatever the frame rate.
+    #[serde(default)]
+    pub frame_times: Vec<(u32, u32)>,
+    /// Input of the fixed ticks spent in [`AppState::Run`], run length encoded.
+    pub input: Vec<(u32, InputFrame)>,
+    /// Upgrades taken, rerolled, skipped and banished on level up, in order.
+    pub upgrades: Vec<UpgradeChoice>,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("Could not access replay file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse RON: {0}")]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    #[error("Could not write RON: {0}")]
+    RonError(#[from] ron::Error),
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let bytes = std::fs::read(path)?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        std::fs::write(path, ron::ser::to_string(self)?)?;
+        Ok(())
+    }
+
+    fn push_input(&mut self, frame: InputFrame) {
+        match self.input.last_mut() {
+            Some((count, last)) if *last == frame => *count += 1,
+            _ => self.input.push((1, frame)),
+        }
+    }
+
+    fn push_frame_time(&mut self, nanos: u32) {
+        match self.frame_times.last_mut() {
+            Some((count, last)) if *last == nanos => *count += 1,
+            _ => self.frame_times.push((1, nanos)),
+        }
+    }
+}
+
+/// Records the current run, and saves it to `path` when the run ends.
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    pub path: Option<PathBuf>,
+    pub replay: Replay,
+}
+
+impl Default for ReplayRecorder {
+    fn default() -> Self {
+        Self {
+            path: Some(DEFAULT_REPLAY_PATH.into()),
+            replay: Replay::default(),
+        }
+    }
+}
+
+/// Replay being played back through [`PlayerControl::Replay`].
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    frame_idx: usize,
+    frame: u32,
+    input_idx: usize,
+    input_tick: u32,
+    upgrade_idx: usize,
+    /// Strategy replaced by the recorded frame times, put back when they run out or the
+    /// run is left.
+    time_strategy: Option<TimeUpdateStrategy>,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            frame_idx: 0,
+            frame: 0,
+            input_idx: 0,
+            input_tick: 0,
+            upgrade_idx: 0,
+            time_strategy: None,
+        }
+    }
+
+    fn rewind(&mut self) {
+        self.frame_idx = 0;
+        self.frame = 0;
+        self.input_idx = 0;
+        self.input_tick = 0;
+        self.upgrade_idx = 0;
+    }
+
+    fn next_frame_time(&mut self) -> Option<u32> {
+        let (count, nanos) = *self.replay.frame_times.get(self.frame_idx)?;
+        self.frame += 1;
+        if self.frame >= count {
+            self.frame_idx += 1;
+            self.frame = 0;
+        }
+        Some(nanos)
+    }
+
+    fn next_input(&mut self) -> Option<InputFrame> {
+        let (count, frame) = *self.replay.input.get(self.input_idx)?;
+        self.input_tick += 1;
+        if self.input_tick >= count {
+            self.input_idx += 1;
+            self.input_tick = 0;
+        }
+        Some(frame)
+    }
+
+    fn next_upgrade(&mut self) -> Option<UpgradeChoice> {
+        let upgrade = *self.replay.upgrades.get(self.upgrade_idx)?;
+        self.upgrade_idx += 1;
+        Some(upgrade)
+    }
+}
+
+pub(crate) fn replay_control(control: Res<PlayerControl>) -> bool {
+    matches!(*control, PlayerControl::Replay(_))
+}
+
+/// The loaded game assets, for hashing.
+#[derive(SystemParam)]
+pub struct GameAssets<'w> {
+    skills: Res<'w, Skills>,
+    skills_assets: Res<'w, Assets<SkillsAsset>>,
+    npc_config: NpcConfig<'w>,
+    pc_handles: Res<'w, PcHandles>,
+    pc_assets: Res<'w, Assets<PlayerCharactersAsset>>,
+    director: Res<'w, SpawnDirector>,
+    waves_assets: Res<'w, Assets<WavesAsset>>,
+}
+
+impl GameAssets<'_> {
+    pub fn hashes(&self) -> AssetHashes {
+        AssetHashes {
+            skills: self
+                .skills_assets
+                .get(&self.skills.handle)
+                .map(|asset| asset.hash)
+                .unwrap_or_default(),
+            npcs: self
+                .npc_config
+                .get()
+                .map(|asset| asset.1)
+                .unwrap_or_default(),
+            pcs: self
+                .pc_assets
+                .get(&self.pc_handles.config)
+                .map(|asset| asset.1)
+                .unwrap_or_default(),
+            waves: self
+                .waves_assets
+                .get(&self.director.config)
+                .map(|asset| asset.hash)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+pub(crate) fn start_recording(
+    seed: Res<RunSeed>,
+    selected: Res<SelectedCharacter>,
+    bonuses: Res<StartingBonuses>,
+    assets: GameAssets,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    recorder.replay = Replay {
+        seed: seed.0,
+        character: selected.0,
+        bonuses: bonuses.0.clone(),
+        assets: assets.hashes(),
+        ..default()
+    };
+}
+
+fn start_playback(
+    assets: GameAssets,
+    mut control: ResMut<PlayerControl>,
+    mut selected: ResMut<SelectedCharacter>,
+    mut bonuses: ResMut<StartingBonuses>,
+) {
+    let PlayerControl::Replay(playback) = &mut *control else {
+        return;
+    };
+    selected.0 = playback.replay.character;
+    bonuses.0 = playback.replay.bonuses.clone();
+    let (recorded, current) = (playback.replay.assets, assets.hashes());
+    for (name, recorded, current) in [
+        ("skills", recorded.skills, current.skills),
+        ("NPCs", recorded.npcs, current.npcs),
+        ("PCs", recorded.pcs, current.pcs),
+        ("waves", recorded.waves, current.waves),
+    ] {
+        if recorded != current {
+            warn!(
+                "The {name} asset differs from the one the replay was recorded with, \
+                the run may diverge."
+            );
+        }
+    }
+}
+
+/// Rewinds the replay once back in the menu, as the time of the first frame of the next
+/// run is taken before it enters [`AppState::Run`].
+fn rewind_playback(mut control: ResMut<PlayerControl>) {
+    if let PlayerControl::Replay(playback) = &mut *control {
+        playback.rewind();
+    }
+}
+
+fn record_frame_time(time: Res<Time>, mut recorder: ResMut<ReplayRecorder>) {
+    let nanos = time.delta().as_nanos().try_into().unwrap_or(u32::MAX);
+    recorder.replay.push_frame_time(nanos);
+}
+
+/// Advances the game time of the next frame by the recorded duration, instead of the
+/// wall clock or headless timestep.
+fn play_frame_time(mut control: ResMut<PlayerControl>, mut strategy: ResMut<TimeUpdateStrategy>) {
+    let PlayerControl::Replay(playback) = &mut *control else {
+        return;
+    };
+    if let Some(nanos) = playback.next_frame_time() {
+        let recorded = TimeUpdateStrategy::ManualDuration(Duration::from_nanos(nanos.into()));
+        let replaced = std::mem::replace(&mut *strategy, recorded);
+        playback.time_strategy.get_or_insert(replaced);
+    } else if let Some(replaced) = playback.time_strategy.take() {
+        *strategy = replaced;
+    }
+}
+
+/// Puts back the time strategy replaced by [`play_frame_time`], so that the frames out of
+/// the run follow the wall clock or headless timestep again.
+fn restore_frame_time(
+    mut control: ResMut<PlayerControl>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+) {
+    if let PlayerControl::Replay(playback) = &mut *control
+        && let Some(replaced) = playback.time_strategy.take()
+    {
+        *strategy = replaced;
+    }
+}
+
+fn record_input(intent: Res<PlayerIntent>, mut recorder: ResMut<ReplayRecorder>) {
+    recorder.replay.push_input(InputFrame {
+        movement: intent.movement.to_array(),
+        jump: intent.jump,
+    });
+}
+
+fn play_input(
+    mut control: ResMut<PlayerControl>,
+    mut intent: ResMut<PlayerIntent>,
+    mut ended: Local<bool>,
+) {
+    let PlayerControl::Replay(playback) = &mut *control else {
+        return;
+    };
+    let frame = playback.next_input();
+    if frame.is_none() && !*ended {
+        info!("Replay input ended.");
+    }
+    *ended = frame.is_none();
+    let frame = frame.unwrap_or_default();
+    intent.movement = Vec2::from_array(frame.movement);
+    intent.jump = frame.jump;
+}
+
+fn record_upgrade(
+    intent: Res<PlayerIntent>,
+    upgrades: Res<SkillUpgradeOptions>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    if let (Some(_), Some(choice)) = (upgrades.entity, intent.upgrade) {
+        recorder.replay.upgrades.push(choice);
+    }
+}
+
+fn play_upgrade(mut control: ResMut<PlayerControl>, mut intent: ResMut<PlayerIntent>) {
+    let PlayerControl::Replay(playback) = &mut *control else {
+        return;
+    };
+    if intent.upgrade.is_none() {
+        intent.upgrade = playback.next_upgrade();
+    }
+}
+
+/// Saves the recorded run to [`ReplayRecorder::path`].
+pub fn save_replay(recorder: Res<ReplayRecorder>, control: Res<PlayerControl>) {
+    if matches!(*control, PlayerControl::Replay(_)) {
+        return;
+    }
+    let Some(path) = &recorder.path else {
+        return;
+    };
+    match recorder.replay.save(path) {
+        Ok(()) => info!("Saved replay to {}.", path.display()),
+        Err(err) => error!("Failed to save replay to {}: {err}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(x: f32, jump: bool) -> InputFrame {
+        InputFrame {
+            movement: [x, 0.],
+            jump,
+        }
+    }
+
+    #[test]
+    fn input_is_run_length_encoded() {
+        let frames = [
+            input(1., false),
+            input(1., false),
+            input(1., true),
+            input(-1., false),
+            input(-1., false),
+            input(-1., false),
+        ];
+        let mut replay = Replay::default();
+        for frame in frames {
+            replay.push_input(frame);
+        }
+        assert_eq!(
+            replay.input,
+            vec![
+                (2, input(1., false)),
+                (1, input(1., true)),
+                (3, input(-1., false))
+            ]
+        );
+
+        let mut playback = ReplayPlayback::new(replay);
+        for frame in frames {
+            assert_eq!(playback.next_input(), Some(frame));
+        }
+        assert_eq!(playback.next_input(), None);
+        playback.rewind();
+        assert_eq!(playback.next_input(), Some(frames[0]));
+    }
+
+    #[test]
+    fn frame_times_are_run_length_encoded() {
+        let nanos = [16_000_000, 16_000_000, 17_000_000, 16_000_000];
+        let mut replay = Replay::default();
+        for frame in nanos {
+            replay.push_frame_time(frame);
+        }
+        assert_eq!(
+            replay.frame_times,
+            vec![(2, 16_000_000), (1, 17_000_000), (1, 16_000_000)]
+        );
+
+        let mut playback = ReplayPlayback::new(replay);
+        for frame in nanos {
+            assert_eq!(playback.next_frame_time(), Some(frame));
+        }
+        assert_eq!(playback.next_frame_time(), None);
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let mut replay = Replay {
+            seed: 42,
+            upgrades: vec![UpgradeChoice::Reroll, UpgradeChoice::Take(1)],
+            ..default()
+        };
+        replay.push_input(input(0.5, true));
+        replay.push_frame_time(15_625_000);
+        let loaded: Replay = ron::de::from_str(&ron::ser::to_string(&replay).unwrap()).unwrap();
+        assert_eq!(loaded.seed, replay.seed);
+        assert_eq!(loaded.input, replay.input);
+        assert_eq!(loaded.frame_times, replay.frame_times);
+        assert_eq!(loaded.upgrades, replay.upgrades);
+    }
+}
@@ -0,0 +1,405 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    app::{AppState, RunState, RunStats},
+    player::{PcHandles, PlayerCharacter, PlayerCharactersAsset, SelectedCharacter},
+    replay::{replay_control, start_recording},
+    skills::{Skill, SkillSpec, Skills},
+};
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveSettings>()
+            .init_resource::<SaveFile>()
+            .init_resource::<StartingBonuses>()
+            .add_systems(Startup, load_save)
+            .add_systems(
+                OnTransition {
+                    exited: AppState::Menu,
+                    entered: AppState::Run,
+                },
+                (
+                    set_starting_bonuses,
+                    check_selected_character.run_if(not(replay_control)),
+                )
+                    .before(start_recording),
+            )
+            .add_systems(
+                OnEnter(AppState::Lost),
+                record_run.run_if(not(replay_control)),
+            )
+            .add_systems(
+                OnEnter(AppState::Won),
+                record_run.run_if(not(replay_control)),
+            );
+    }
+}
+
+/// Current version of the [`SaveFile`] schema, bumped on breaking changes, with a
+/// [`migrate`] step from the previous version.
+pub const SAVE_VERSION: u32 = 1;
+
+/// Where the [`SaveFile`] is kept. Defaults to `swarm-hole/save.ron` in the user data
+/// dir, set with `--save <path>`. Nothing is loaded or saved without a path, like in
+/// headless runs.
+#[derive(Resource)]
+pub struct SaveSettings {
+    pub path: Option<PathBuf>,
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        Self {
+            path: default_save_path(),
+        }
+    }
+}
+
+fn default_save_path() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let data_dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(".local/share")))
+    };
+    data_dir.map(|dir| dir.join("swarm-hole").join("save.ron"))
+}
+
+/// Totals over all runs.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub runs: u32,
+    pub wins: u32,
+    pub kills: u32,
+    pub bosses_killed: u32,
+    /// Time spent in runs, in seconds.
+    pub run_time: f32,
+    pub best_level: u32,
+    pub xp: u64,
+}
+
+/// Progress kept between runs.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveFile {
+    pub version: u32,
+    pub stats: LifetimeStats,
+    /// Earned at the end of each run, spent on characters and bonuses.
+    pub currency: u32,
+    /// Names of the characters of `all.pcs.ron` bought with currency.
+    pub unlocked: BTreeSet<String>,
+    /// Levels bought of the bonuses of `all.skills.ron`, by name.
+    pub bonuses: BTreeMap<String, u32>,
+}
+
+impl Default for SaveFile {
+    fn default() -> Self {
+        Self {
+            version: SAVE_VERSION,
+            stats: LifetimeStats::default(),
+            currency: 0,
+            unlocked: BTreeSet::new(),
+            bonuses: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SaveHeader {
+    /// Missing from saves of version 0.
+    #[serde(default)]
+    version: u32,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("Could not access save file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse RON: {0}")]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    #[error("Could not read or write RON: {0}")]
+    RonError(#[from] ron::Error),
+    #[error("Save file version {0} is newer than the supported version {SAVE_VERSION}")]
+    NewerVersion(u32),
+    #[error("Save file version {0} can not be migrated")]
+    UnknownVersion(u32),
+}
+
+impl SaveFile {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        let bytes = std::fs::read(path)?;
+        let version = ron::de::from_bytes::<SaveHeader>(&bytes)?.version;
+        if version == SAVE_VERSION {
+            return Ok(ron::de::from_bytes(&bytes)?);
+        }
+        let save = migrate(version, ron::de::from_bytes(&bytes)?)?;
+        let mut save = save.into_rust::<SaveFile>()?;
+        save.version = SAVE_VERSION;
+        Ok(save)
+    }
+
+    /// Writes to a temporary file next to `path` first, so that a crash while saving
+    /// doesn't lose the previous save.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        std::fs::write(&tmp_path, ron)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    pub fn is_unlocked(&self, pc: &PlayerCharacter) -> bool {
+        pc.unlock_cost == 0 || self.unlocked.contains(&pc.name)
+    }
+
+    /// Spends `cost` if there is enough currency.
+    pub fn spend(&mut self, cost: u32) -> bool {
+        if self.currency < cost {
+            return false;
+        }
+        self.currency -= cost;
+        true
+    }
+}
+
+/// Upgrades the fields of a save written with schema `version` to the current schema,
+/// one version at a time. Each schema change adds a step from the version before it.
+fn migrate(mut version: u32, mut save: ron::Value) -> Result<ron::Value, SaveError> {
+    if version > SAVE_VERSION {
+        return Err(SaveError::NewerVersion(version));
+    }
+    while version < SAVE_VERSION {
+        save = match version {
+            0 => migrate_v0(save),
+            version => return Err(SaveError::UnknownVersion(version)),
+        };
+        version += 1;
+    }
+    Ok(save)
+}
+
+/// Version 0 saves have no `version` field.
+fn migrate_v0(mut save: ron::Value) -> ron::Value {
+    if let ron::Value::Map(fields) = &mut save {
+        fields.insert(
+            ron::Value::String("version".to_string()),
+            ron::Value::Number(1.into()),
+        );
+    }
+    save
+}
+
+/// Writes `save` to the path of `settings`, if any.
+pub fn write_save(save: &SaveFile, settings: &SaveSettings) {
+    let Some(path) = &settings.path else {
+        return;
+    };
+    match save.save(path) {
+        Ok(()) => info!("Saved progress to {}.", path.display()),
+        Err(err) => error!("Could not save progress to {}: {err}", path.display()),
+    }
+}
+
+fn load_save(mut settings: ResMut<SaveSettings>, mut save: ResMut<SaveFile>) {
+    let Some(path) = &settings.path else {
+        return;
+    };
+    if !path.exists() {
+        return;
+    }
+    match SaveFile::load(path) {
+        Ok(loaded) => {
+            info!("Loaded progress from {}.", path.display());
+            *save = loaded;
+        }
+        Err(err) => {
+            // keeps the unreadable file instead of overwriting it at the end of the run
+            error!("Could not load progress from {}: {err}", path.display());
+            settings.path = None;
+        }
+    }
+}
+
+/// Bonus levels the current run started with, taken from the [`SaveFile`] or from the
+/// replay being played back.
+#[derive(Resource, Clone, Default, Debug)]
+pub struct StartingBonuses(pub BTreeMap<String, u32>);
+
+impl StartingBonuses {
+    /// Specs added to the player's skills, once per bonus level.
+    pub fn specs(&self, skills: &Skills) -> Vec<(Skill, SkillSpec)> {
+        let mut specs = Vec::new();
+        for (name, level) in &self.0 {
+            let Some(bonus) = skills.bonuses.get(name) else {
+                warn!("Bonus {name:?} not found.");
+                continue;
+            };
+            for _ in 0..*level {
+                specs.extend(bonus.skills.iter().map(|(s, spec)| (*s, spec.clone())));
+            }
+        }
+        specs
+    }
+}
+
+fn set_starting_bonuses(save: Res<SaveFile>, mut bonuses: ResMut<StartingBonuses>) {
+    bonuses.0 = save.bonuses.clone();
+}
+
+/// Plays the first unlocked character instead of a locked one, like one passed with
+/// `--character`. Without a save file all characters can be played.
+fn check_selected_character(
+    settings: Res<SaveSettings>,
+    save: Res<SaveFile>,
+    pc_handles: Res<PcHandles>,
+    pc_assets: Res<Assets<PlayerCharactersAsset>>,
+    mut selected: ResMut<SelectedCharacter>,
+) {
+    if settings.path.is_none() {
+        return;
+    }
+    let Some(pcs) = pc_assets.get(&pc_handles.config) else {
+        return;
+    };
+    let Some(pc) = pcs.0.get(selected.0).filter(|pc| !save.is_unlocked(pc)) else {
+        return;
+    };
+    match pcs.0.iter().position(|pc| save.is_unlocked(pc)) {
+        Some(idx) => {
+            error!(
+                "Character {:?} is locked, playing {:?} instead.",
+                pc.name, pcs.0[idx].name
+            );
+            selected.0 = idx;
+        }
+        None => error!("Character {:?} is locked, but so are all others.", pc.name),
+    }
+}
+
+/// Currency earned by a run: a coin per 10 kills, 25 per boss, and 50 for a win.
+pub fn run_currency(run_state: &RunState, run_stats: &RunStats, won: bool) -> u32 {
+    let kills = run_stats.kills.values().sum::<u32>();
+    kills / 10 + run_state.bosses_killed * 25 + if won { 50 } else { 0 }
+}
+
+/// Adds the run that ended to the [`SaveFile`] and saves it. Not run for replays being
+/// played back, so they don't earn currency.
+fn record_run(
+    app_state: Res<State<AppState>>,
+    run_state: Res<RunState>,
+    settings: Res<SaveSettings>,
+    mut run_stats: ResMut<RunStats>,
+    mut save: ResMut<SaveFile>,
+) {
+    let won = *app_state.get() == AppState::Won;
+    run_stats.currency = run_currency(&run_state, &run_stats, won);
+    save.currency += run_stats.currency;
+    let stats = &mut save.stats;
+    stats.runs += 1;
+    if won {
+        stats.wins += 1;
+    }
+    stats.kills += run_stats.kills.values().sum::<u32>();
+    stats.bosses_killed += run_state.bosses_killed;
+    stats.run_time += run_state.run_time.as_secs_f32();
+    stats.best_level = stats.best_level.max(run_stats.level);
+    stats.xp += run_stats.xp as u64;
+    write_save(&save, &settings);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory in the system temp dir for the files of test `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("swarm-hole-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn migrate_versionless_save() {
+        let dir = temp_dir("migrate");
+        let path = dir.join("save.ron");
+        std::fs::write(
+            &path,
+            "(stats: (runs: 3, wins: 1), currency: 120, unlocked: [\"Rogue\"])",
+        )
+        .unwrap();
+        let save = SaveFile::load(&path).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.currency, 120);
+        assert_eq!(save.stats.runs, 3);
+        assert_eq!(save.stats.wins, 1);
+        assert!(save.unlocked.contains("Rogue"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reject_newer_version() {
+        let dir = temp_dir("newer");
+        let path = dir.join("save.ron");
+        std::fs::write(
+            &path,
+            format!("(version: {}, currency: 5)", SAVE_VERSION + 1),
+        )
+        .unwrap();
+        assert!(matches!(
+            SaveFile::load(&path),
+            Err(SaveError::NewerVersion(version)) if version == SAVE_VERSION + 1
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = temp_dir("round-trip");
+        let path = dir.join("nested").join("save.ron");
+        let mut save = SaveFile {
+            currency: 42,
+            ..default()
+        };
+        save.stats.kills = 1000;
+        save.stats.run_time = 61.5;
+        save.unlocked.insert("Rogue".to_string());
+        save.bonuses.insert("Might".to_string(), 2);
+        save.save(&path).unwrap();
+        // saving again replaces the previous save
+        save.currency = 43;
+        save.save(&path).unwrap();
+
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        assert!(!Path::new(&tmp_path).exists());
+        let loaded = SaveFile::load(&path).unwrap();
+        assert_eq!(loaded.version, SAVE_VERSION);
+        assert_eq!(loaded.currency, 43);
+        assert_eq!(loaded.stats.kills, 1000);
+        assert_eq!(loaded.stats.run_time, 61.5);
+        assert_eq!(loaded.unlocked, save.unlocked);
+        assert_eq!(loaded.bonuses, save.bonuses);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,89 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use crate::{
+    app::{AppState, Headless},
+    physics::enemy_layer,
+    player::Player,
+};
+
+use super::{
+    CombatSystems, ground_circle_isometry,
+    health::DamageType,
+    status::{Hit, HitWriter},
+};
+
+pub struct AuraPlugin;
+
+impl Plugin for AuraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                update_aura,
+                draw_aura.run_if(not(resource_exists::<Headless>)),
+            )
+                .in_set(CombatSystems::Aura)
+                .run_if(in_state(AppState::Run)),
+        );
+    }
+}
+
+const PLAYER_AURA_COLOR: Color = Color::srgb(0.2, 1.0, 0.6);
+const NPC_AURA_COLOR: Color = Color::srgb(1.0, 0.3, 0.6);
+
+/// Continuously damages the enemies in `range` of the owner.
+#[derive(Component, Reflect, Default)]
+pub struct Aura {
+    pub range: f32,
+    pub dps: f32,
+    pub status_duration: f32,
+}
+
+fn update_aura(
+    time: Res<Time>,
+    q_space: SpatialQuery,
+    q_aura: Query<(Entity, &Aura, &Transform, Has<Player>)>,
+    mut hits: HitWriter,
+) {
+    for (source, aura, tr_src, is_player) in &q_aura {
+        if aura.range <= 0. {
+            continue;
+        }
+        for target in q_space.shape_intersections(
+            &Collider::sphere(aura.range),
+            tr_src.translation,
+            Quat::default(),
+            &SpatialQueryFilter::from_mask([enemy_layer(is_player)]),
+        ) {
+            hits.write::<Aura>(
+                Hit {
+                    target,
+                    source,
+                    damage: time.delta_secs() * aura.dps,
+                    kind: DamageType::Energy,
+                    crit: false,
+                    knockback: Vec3::ZERO,
+                },
+                aura.status_duration,
+            );
+        }
+    }
+}
+
+fn draw_aura(q_aura: Query<(&Aura, &Transform, Has<Player>)>, mut gizmos: Gizmos) {
+    for (aura, tr_src, is_player) in &q_aura {
+        if aura.range <= 0. {
+            continue;
+        }
+        gizmos.circle(
+            ground_circle_isometry(tr_src.translation),
+            aura.range,
+            if is_player {
+                PLAYER_AURA_COLOR
+            } else {
+                NPC_AURA_COLOR
+            },
+        );
+    }
+}
@@ -1,35 +1,55 @@
+use std::f32::consts::TAU;
+
 use avian3d::prelude::*;
 use bevy::prelude::*;
+use rand::prelude::*;
+use serde::Deserialize;
 
 use crate::{
-    app::{AppState, InGame, RunState},
+    app::{AppState, RunRng, RunState, RunStats},
+    boss::Boss,
     npc::Npc,
-    physics::Layer,
-    player::Player,
+    player::{Player, move_player},
 };
 
 use super::{
-    EquippedSkills, IsSkill, Skill, apply_skill_specs,
-    xp::{XpDrop, XpDrops},
+    CombatSystems, EquippedSkills, Skills,
+    pickup::{PickupKind, Pickups},
+    registry::Skill,
+    status::StatusEffects,
 };
 
 pub struct HealthPlugin;
 
 impl Plugin for HealthPlugin {
     fn build(&self, app: &mut App) {
-        app.add_state_scoped_event::<TakeDamageEvent>(AppState::Run)
+        app.register_type::<DamageType>()
+            .add_state_scoped_event::<TakeDamageEvent>(AppState::Run)
+            .add_state_scoped_event::<KillEvent>(AppState::Run)
             .add_systems(
                 Update,
                 (
-                    apply_skill_specs::<MaxHealth>,
-                    apply_skill_specs::<HealthRegen>,
                     init_health,
-                    take_damage,
+                    take_damage.after(CombatSystems::Status),
                     regen_health,
                     die,
+                    (credit_kills, heal_on_kill),
                 )
+                    .chain()
+                    .run_if(in_state(AppState::Run)),
+            )
+            .add_systems(
+                FixedUpdate,
+                apply_knockback
+                    .after(move_player)
                     .run_if(in_state(AppState::Run)),
-            );
+            )
+            .add_systems(
+                FixedPostUpdate,
+                end_knockback.after(PhysicsSet::StepSimulation),
+            )
+            .add_systems(OnEnter(AppState::Won), log_skill_stats)
+            .add_systems(OnEnter(AppState::Lost), log_skill_stats);
     }
 }
 
@@ -38,21 +58,27 @@ pub struct MaxHealth {
     pub max_hp: u32,
 }
 
-impl IsSkill for MaxHealth {
-    fn skill() -> Skill {
-        Skill::Health
-    }
-}
-
 #[derive(Component)]
+#[require(StatusEffects, LastHit, Knockback)]
 pub struct Health(pub f32);
 
+/// Attacker of the last hit taken, credited with the kill.
+#[derive(Component, Default)]
+pub struct LastHit {
+    pub source: Option<Entity>,
+    pub skill: Option<Skill>,
+}
+
 fn init_health(
+    skills: Res<Skills>,
     q_health: Query<(Entity, &MaxHealth, &EquippedSkills), Without<Health>>,
     mut cmd: Commands,
 ) {
     for (ent, max_health, equipped) in &q_health {
-        if equipped.is_equipped(Skill::Health) {
+        if skills
+            .bound_skills::<MaxHealth>()
+            .any(|skill| equipped.is_equipped(skill))
+        {
             cmd.entity(ent).insert(Health(max_health.max_hp as f32));
         }
     }
@@ -63,12 +89,6 @@ pub struct HealthRegen {
     pub hp_per_sec: f32,
 }
 
-impl IsSkill for HealthRegen {
-    fn skill() -> Skill {
-        Skill::HealthRegen
-    }
-}
-
 fn regen_health(time: Res<Time>, mut q_regen: Query<(&mut Health, &MaxHealth, &HealthRegen)>) {
     for (mut health, max_health, health_regen) in &mut q_regen {
         health.0 =
@@ -76,60 +96,190 @@ fn regen_health(time: Res<Time>, mut q_regen: Query<(&mut Health, &MaxHealth, &H
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Reflect, Deserialize)]
+pub enum DamageType {
+    Physical,
+    Energy,
+}
+
+/// Damage reduction in percent per [`DamageType`]. Negative values make the owner weak
+/// against that type.
+#[derive(Component, Reflect, Default)]
+pub struct Resistances {
+    pub physical: f32,
+    pub energy: f32,
+}
+
+impl Resistances {
+    pub fn damage_multiplier(&self, kind: DamageType) -> f32 {
+        let resistance = match kind {
+            DamageType::Physical => self.physical,
+            DamageType::Energy => self.energy,
+        };
+        1. - resistance.min(100.) / 100.
+    }
+}
+
+/// Chance in percent of the owner's hits to crit, multiplying their damage.
+#[derive(Component, Reflect, Default)]
+pub struct CriticalHits {
+    pub crit_chance: f32,
+    pub crit_multiplier: f32,
+}
+
+impl CriticalHits {
+    /// Rolls a hit of an attacker with `crit`, returning whether it crits and its damage
+    /// multiplier.
+    pub fn roll(crit: Option<&Self>, rng: &mut impl Rng) -> (bool, f32) {
+        match crit {
+            Some(crit) if rng.gen_range(0. ..100.) < crit.crit_chance => {
+                (true, crit.crit_multiplier.max(1.))
+            }
+            _ => (false, 1.),
+        }
+    }
+}
+
 #[derive(Event)]
 pub struct TakeDamageEvent {
     pub target: Entity,
+    /// Entity whose skill dealt the damage.
+    pub source: Entity,
+    /// Skill that dealt the damage, found by [`HitWriter`](super::status::HitWriter).
+    pub skill: Option<Skill>,
+    /// Damage before the target's [`Resistances`].
     pub damage: f32,
+    pub kind: DamageType,
+    pub crit: bool,
+    /// Displacement of the target, see [`Knockback`].
+    pub knockback: Vec3,
+}
+
+/// Sent by [`die`] for every NPC or player killed, crediting the attacker of its
+/// [`LastHit`].
+#[derive(Event)]
+pub struct KillEvent {
+    pub target: Entity,
+    pub source: Option<Entity>,
+    pub skill: Option<Skill>,
+    /// Name of the killed NPC, none for the player.
+    pub npc: Option<String>,
+    pub is_boss: bool,
+}
+
+/// Knockback of the hits taken, moving the owner over the next fixed step. It is added
+/// to the [`LinearVelocity`] after the movement systems set it, and taken back once
+/// physics moved the owner, so they neither cancel it nor keep it.
+#[derive(Component, Default)]
+pub struct Knockback {
+    /// Displacement of the hits taken since the last fixed step.
+    pending: Vec3,
+    /// Velocity added for the current fixed step.
+    applied: Vec3,
 }
 
-fn take_damage(mut ev_take_damage: EventReader<TakeDamageEvent>, mut q_health: Query<&mut Health>) {
-    for TakeDamageEvent { target, damage } in ev_take_damage.read() {
-        if let Ok(mut health) = q_health.get_mut(*target) {
-            health.0 = if *damage >= health.0 {
-                0.
-            } else {
-                health.0 - damage
-            };
+fn take_damage(
+    mut ev_take_damage: EventReader<TakeDamageEvent>,
+    mut q_health: Query<(
+        &mut Health,
+        &mut LastHit,
+        &mut Knockback,
+        Option<&Resistances>,
+    )>,
+    mut run_stats: ResMut<RunStats>,
+    q_player: Query<(), With<Player>>,
+) {
+    for ev in ev_take_damage.read() {
+        let Ok((mut health, mut last_hit, mut knockback, resistances)) =
+            q_health.get_mut(ev.target)
+        else {
+            continue;
+        };
+        let damage = ev.damage * resistances.map_or(1., |res| res.damage_multiplier(ev.kind));
+        if damage > 0. && health.0 > 0. {
+            last_hit.source = Some(ev.source);
+            last_hit.skill = ev.skill;
+            if let Some(skill) = ev.skill {
+                let damage = damage.min(health.0);
+                if q_player.contains(ev.source) {
+                    *run_stats.damage_dealt.entry(skill).or_default() += damage;
+                } else if q_player.contains(ev.target) {
+                    *run_stats.damage_taken.entry(skill).or_default() += damage;
+                }
+            }
         }
+        health.0 = if damage >= health.0 {
+            0.
+        } else {
+            health.0 - damage
+        };
+        knockback.pending += ev.knockback;
     }
 }
 
-fn die(
+fn apply_knockback(
+    time: Res<Time<Fixed>>,
+    mut q_knockback: Query<(&mut Knockback, &mut LinearVelocity)>,
+) {
+    for (mut knockback, mut lin_vel) in &mut q_knockback {
+        if knockback.pending == Vec3::ZERO || time.delta_secs() <= 0. {
+            continue;
+        }
+        knockback.applied = knockback.pending / time.delta_secs();
+        knockback.pending = Vec3::ZERO;
+        lin_vel.0 += knockback.applied;
+    }
+}
+
+fn end_knockback(mut q_knockback: Query<(&mut Knockback, &mut LinearVelocity)>) {
+    for (mut knockback, mut lin_vel) in &mut q_knockback {
+        if knockback.applied != Vec3::ZERO {
+            lin_vel.0 -= knockback.applied;
+            knockback.applied = Vec3::ZERO;
+        }
+    }
+}
+
+pub(crate) fn die(
     mut next_state: ResMut<NextState<AppState>>,
     mut run_state: ResMut<RunState>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    xp_drops: Res<XpDrops>,
-    q_npc: Query<(Entity, &Health, &Transform, Option<&Npc>, Has<Player>)>,
+    mut rng: ResMut<RunRng>,
+    mut ev_kill: EventWriter<KillEvent>,
+    pickups: Res<Pickups>,
+    q_npc: Query<(
+        Entity,
+        &Health,
+        &LastHit,
+        &Transform,
+        Option<&Npc>,
+        Has<Player>,
+        Has<Boss>,
+    )>,
     mut cmd: Commands,
 ) {
-    for (npc_ent, health, tr_npc, npc, is_player) in &q_npc {
+    for (npc_ent, health, last_hit, tr_npc, npc, is_player, is_boss) in &q_npc {
         if health.0 < 0.9 {
+            ev_kill.write(KillEvent {
+                target: npc_ent,
+                source: last_hit.source,
+                skill: last_hit.skill,
+                npc: npc.map(|npc| npc.name.clone()),
+                is_boss,
+            });
             if let Some(npc) = npc {
                 run_state.live_npcs -= 1;
-
-                let h = XpDrop::get_height(npc.xp_drop);
                 let p = tr_npc.translation;
-                let id = cmd
-                    .spawn((
-                        XpDrop(npc.xp_drop),
-                        Mesh3d(meshes.add(Sphere::new(h).mesh().ico(4).unwrap())),
-                        MeshMaterial3d(if XpDrop::is_big(npc.xp_drop) {
-                            xp_drops.xp_drop_big.clone()
-                        } else {
-                            xp_drops.xp_drop_small.clone()
-                        }),
-                        Transform::from_translation(Vec3::new(p.x, h + 0.02, p.z)),
-                        RigidBody::Kinematic,
-                        Collider::sphere(h),
-                        CollisionLayers::new([Layer::Building], [Layer::Building, Layer::Player]),
-                        StateScoped(InGame),
-                    ))
-                    .id();
-                cmd.entity(id)
-                    .insert(Name::new(format!("Xp Drop of {} ({id:?})", npc.xp_drop)));
-
-                if run_state.live_npcs == 0 {
-                    next_state.set(AppState::Won);
+                if npc.xp_drop > 0 {
+                    pickups.spawn(PickupKind::Xp(npc.xp_drop), p, &mut cmd);
+                }
+                for drop in &npc.drops {
+                    if rng.loot.gen_bool(drop.chance as f64) {
+                        let offset = Vec2::from_angle(rng.loot.gen_range(0. ..TAU)) * 1.5;
+                        pickups.spawn(drop.pickup, p + Vec3::new(offset.x, 0., offset.y), &mut cmd);
+                    }
+                }
+                if is_boss {
+                    run_state.bosses_killed += 1;
                 }
             } else if is_player {
                 next_state.set(AppState::Lost);
@@ -138,3 +288,61 @@ fn die(
         }
     }
 }
+
+fn credit_kills(
+    mut ev_kill: EventReader<KillEvent>,
+    mut run_stats: ResMut<RunStats>,
+    q_player: Query<(), With<Player>>,
+) {
+    for ev in ev_kill.read() {
+        if let Some(npc) = &ev.npc {
+            match run_stats.kills.get_mut(npc) {
+                Some(kills) => *kills += 1,
+                None => {
+                    run_stats.kills.insert(npc.clone(), 1);
+                }
+            }
+        }
+        if let (Some(source), Some(skill)) = (ev.source, ev.skill)
+            && q_player.contains(source)
+        {
+            *run_stats.skill_kills.entry(skill).or_default() += 1;
+        }
+    }
+}
+
+/// Health restored to the owner for each kill of its skills.
+#[derive(Component, Reflect, Default)]
+pub struct HealOnKill {
+    pub hp_per_kill: f32,
+}
+
+fn heal_on_kill(
+    mut ev_kill: EventReader<KillEvent>,
+    mut q_heal: Query<(&mut Health, &MaxHealth, &HealOnKill)>,
+) {
+    for ev in ev_kill.read() {
+        if let Some(source) = ev.source
+            && let Ok((mut health, max_health, heal)) = q_heal.get_mut(source)
+            && health.0 > 0.
+        {
+            health.0 = (health.0 + heal.hp_per_kill).min(max_health.max_hp as f32);
+        }
+    }
+}
+
+fn log_skill_stats(skills: Res<Skills>, run_stats: Res<RunStats>) {
+    let mut damage = run_stats.damage_dealt.iter().collect::<Vec<_>>();
+    damage.sort_by(|(_, d1), (_, d2)| d2.total_cmp(d1));
+    for (skill, damage) in damage {
+        info!(
+            "{} dealt {damage:.0} damage and killed {}.",
+            skills.ui_name(*skill).unwrap_or(skill.name()),
+            run_stats
+                .skill_kills
+                .get(skill)
+                .copied()
+                .unwrap_or_default()
+        );
+    }
+}
@@ -6,14 +6,18 @@ use bevy::{
 };
 
 use crate::{
-    app::{AppState, InGame},
+    app::{AppState, Headless, InGame, RunRng},
     npc::Npc,
     physics::Layer,
     player::Player,
     vfx::DamageParticlesEvent,
 };
 
-use super::{IsSkill, Skill, apply_skill_specs, health::TakeDamageEvent};
+use super::{
+    CombatSystems,
+    health::{CriticalHits, DamageType},
+    status::{Disarmed, Hit, HitWriter},
+};
 
 pub struct LaserPlugin;
 
@@ -21,19 +25,21 @@ impl Plugin for LaserPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<LaserState>()
             .init_resource::<LaserHandles>()
-            .add_systems(Startup, setup_assets)
+            .add_event::<DamageParticlesEvent>()
+            .add_systems(
+                Startup,
+                setup_assets.run_if(not(resource_exists::<Headless>)),
+            )
             .add_systems(
                 Update,
                 (
-                    apply_skill_specs::<Laser>,
-                    (
-                        (laser_target_npc, laser_target_player),
-                        laser_shoot_ray,
-                        laser_ray_update,
-                        laser_ray_despawn,
-                    )
-                        .chain(),
+                    (laser_target_npc, laser_target_player),
+                    laser_shoot_ray,
+                    laser_ray_update,
+                    laser_ray_despawn,
                 )
+                    .chain()
+                    .in_set(CombatSystems::Laser)
                     .run_if(in_state(AppState::Run)),
             );
     }
@@ -86,12 +92,7 @@ pub struct Laser {
     pub dps: f32,
     pub duration: f32,
     pub cooldown: f32,
-}
-
-impl IsSkill for Laser {
-    fn skill() -> Skill {
-        Skill::Laser
-    }
+    pub status_duration: f32,
 }
 
 #[derive(Component, Reflect, Default)]
@@ -104,11 +105,13 @@ pub struct LaserState {
 fn laser_target_npc(
     time: Res<Time>,
     q_space: SpatialQuery,
-    mut q_laser: Query<(&mut LaserState, &Laser, &Transform), With<Player>>,
+    mut q_laser: Query<(&mut LaserState, &Laser, &Transform, Has<Disarmed>), With<Player>>,
     q_npc: Query<&Transform, With<Npc>>,
 ) {
-    for (mut laser, laser_config, tr_player) in &mut q_laser {
-        if laser.target.is_some() || time.elapsed_secs() - laser.time_ended < laser_config.cooldown
+    for (mut laser, laser_config, tr_player, is_disarmed) in &mut q_laser {
+        if is_disarmed
+            || laser.target.is_some()
+            || time.elapsed_secs() - laser.time_ended < laser_config.cooldown
         {
             continue;
         }
@@ -136,11 +139,13 @@ fn laser_target_npc(
 
 fn laser_target_player(
     time: Res<Time>,
-    mut q_laser: Query<(&mut LaserState, &Laser, &Transform), Without<Player>>,
+    mut q_laser: Query<(&mut LaserState, &Laser, &Transform, Has<Disarmed>), Without<Player>>,
     q_player: Query<(Entity, &Transform), With<Player>>,
 ) {
-    for (mut laser, laser_config, tr_src) in &mut q_laser {
-        if laser.target.is_some() || time.elapsed_secs() - laser.time_ended < laser_config.cooldown
+    for (mut laser, laser_config, tr_src, is_disarmed) in &mut q_laser {
+        if is_disarmed
+            || laser.target.is_some()
+            || time.elapsed_secs() - laser.time_ended < laser_config.cooldown
         {
             continue;
         }
@@ -168,6 +173,9 @@ pub struct LaserRay {
     pub source: Entity,
     pub target: Entity,
     pub time_started: f32,
+    /// Whether the whole ray crits, multiplying its dps by `damage_multiplier`.
+    pub crit: bool,
+    pub damage_multiplier: f32,
     pub dead: bool,
     pub vfx_started: bool,
 }
@@ -178,7 +186,9 @@ pub struct LaserRayMesh;
 fn laser_shoot_ray(
     time: Res<Time>,
     weapons: Res<LaserHandles>,
+    mut rng: ResMut<RunRng>,
     mut q_laser: Query<(Entity, &mut LaserState, Has<Player>)>,
+    q_crit: Query<&CriticalHits>,
     mut cmd: Commands,
 ) {
     for (source, mut laser, is_player) in &mut q_laser {
@@ -186,12 +196,16 @@ fn laser_shoot_ray(
             let Some(target) = laser.target else {
                 continue;
             };
+            let (crit, damage_multiplier) =
+                CriticalHits::roll(q_crit.get(source).ok(), &mut rng.combat);
             let id = cmd
                 .spawn((
                     LaserRay {
                         source,
                         target,
                         time_started: time.elapsed_secs(),
+                        crit,
+                        damage_multiplier,
                         dead: false,
                         vfx_started: false,
                     },
@@ -236,13 +250,13 @@ fn laser_ray_update(
         (&Transform, Option<&Laser>, Has<Player>),
         (Without<LaserRay>, Without<LaserRayMesh>),
     >,
-    mut ev_take_damage: EventWriter<TakeDamageEvent>,
+    mut hits: HitWriter,
 ) {
     for (mut ray, mut tr_ray, children) in &mut q_ray {
         if ray.dead {
             continue;
         };
-        let (s, dps, duration, color) = {
+        let (s, dps, duration, status_duration, color) = {
             let Ok((tr_laser, Some(laser_config), is_player)) = q_targets.get(ray.source) else {
                 ray.dead = true;
                 continue;
@@ -251,6 +265,7 @@ fn laser_ray_update(
                 tr_laser.translation + if is_player { Vec3::Y * 0.8 } else { Vec3::ZERO },
                 laser_config.dps,
                 laser_config.duration,
+                laser_config.status_duration,
                 if is_player {
                     PLAYER_LASER_COLOR
                 } else {
@@ -284,7 +299,9 @@ fn laser_ray_update(
 
         *vis_ray_mesh = Visibility::Visible;
 
-        if !ray.vfx_started {
+        // the status is inflicted once per ray
+        let first_hit = !ray.vfx_started;
+        if first_hit {
             ray.vfx_started = true;
             ev_damage_particles.write(DamageParticlesEvent {
                 position: t - dir * 0.5,
@@ -293,10 +310,17 @@ fn laser_ray_update(
             });
         }
 
-        ev_take_damage.write(TakeDamageEvent {
-            target: ray.target,
-            damage: time.delta_secs() * dps,
-        });
+        hits.write::<Laser>(
+            Hit {
+                target: ray.target,
+                source: ray.source,
+                damage: time.delta_secs() * dps * ray.damage_multiplier,
+                kind: DamageType::Energy,
+                crit: ray.crit,
+                knockback: Vec3::ZERO,
+            },
+            if first_hit { status_duration } else { 0. },
+        );
     }
 }
 
@@ -3,15 +3,21 @@ use bevy::prelude::*;
 
 use crate::{app::AppState, npc::Npc, physics::Layer};
 
-use super::{IsSkill, Skill, apply_skill_specs, health::TakeDamageEvent};
+use super::{
+    CombatSystems,
+    health::DamageType,
+    status::{Disarmed, Hit, HitWriter},
+};
 
 pub struct MeleePlugin;
 
 impl Plugin for MeleePlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<Melee>().add_systems(
+        app.add_systems(
             Update,
-            (apply_skill_specs::<Melee>, update_melee).run_if(in_state(AppState::Run)),
+            update_melee
+                .in_set(CombatSystems::Melee)
+                .run_if(in_state(AppState::Run)),
         );
     }
 }
@@ -22,19 +28,16 @@ pub struct Melee {
     pub dps: u32,
 }
 
-impl IsSkill for Melee {
-    fn skill() -> Skill {
-        Skill::Melee
-    }
-}
-
 fn update_melee(
     time: Res<Time>,
     q_space: SpatialQuery,
-    q_melee: Query<(&Melee, &Transform), With<Npc>>,
-    mut ev_take_damage: EventWriter<TakeDamageEvent>,
+    q_melee: Query<(Entity, &Melee, &Transform, Has<Disarmed>), With<Npc>>,
+    mut hits: HitWriter,
 ) {
-    for (melee, tr_npc) in &q_melee {
+    for (npc_ent, melee, tr_npc, is_disarmed) in &q_melee {
+        if is_disarmed {
+            continue;
+        }
         let pos = tr_npc.translation;
         for player_ent in q_space.shape_intersections(
             &Collider::sphere(melee.range),
@@ -42,10 +45,17 @@ fn update_melee(
             Quat::default(),
             &SpatialQueryFilter::from_mask([Layer::Player]),
         ) {
-            ev_take_damage.write(TakeDamageEvent {
-                target: player_ent,
-                damage: time.delta_secs() * melee.dps as f32,
-            });
+            hits.write::<Melee>(
+                Hit {
+                    target: player_ent,
+                    source: npc_ent,
+                    damage: time.delta_secs() * melee.dps as f32,
+                    kind: DamageType::Physical,
+                    crit: false,
+                    knockback: Vec3::ZERO,
+                },
+                0.,
+            );
         }
     }
 }
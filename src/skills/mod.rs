@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{any::TypeId, collections::VecDeque, fmt};
 
 use bevy::{
     app::PluginGroupBuilder,
@@ -8,22 +8,45 @@ use bevy::{
     prelude::*,
 };
 use rand::prelude::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::app::AppState;
+use crate::{
+    app::{AppState, RunRng, RunStats},
+    player::{Player, PlayerIntent},
+    replay::AssetHash,
+};
 
 use self::{
-    health::HealthPlugin,
-    laser::LaserPlugin,
-    melee::MeleePlugin,
-    swarm::SwarmPlugin,
-    xp::{XpGatherState, XpPlugin},
+    aura::{Aura, AuraPlugin},
+    health::{CriticalHits, HealOnKill, HealthPlugin, HealthRegen, MaxHealth, Resistances, die},
+    laser::{Laser, LaserPlugin},
+    melee::{Melee, MeleePlugin},
+    modifiers::{AttributeValues, ModifiersPlugin, SkillBase, SkillModifiers},
+    nova::{Nova, NovaPlugin},
+    passive::{PassiveMeta, PassivePlugin, set_passive_modifiers},
+    pickup::PickupPlugin,
+    projectile::{Projectile, ProjectilePlugin},
+    status::{Stacking, StatusEffect, StatusMeta, StatusPlugin},
+    swarm::{Swarm, SwarmPlugin},
+    xp::{XpCurve, XpGather, XpGatherState, XpPlugin},
+};
+
+pub use self::registry::{
+    Attribute, Passive, RegisterSkillComponent, Skill, SkillRegistry, Status,
 };
 
+pub mod aura;
 pub mod health;
 pub mod laser;
 pub mod melee;
+pub mod modifiers;
+pub mod nova;
+pub mod passive;
+pub mod pickup;
+pub mod projectile;
+pub mod registry;
+pub mod status;
 pub mod swarm;
 pub mod xp;
 
@@ -36,25 +59,86 @@ impl PluginGroup for SkillPluginGroup {
             .add(HealthPlugin)
             .add(SwarmPlugin)
             .add(XpPlugin)
+            .add(PickupPlugin)
             .add(LaserPlugin)
             .add(MeleePlugin)
+            .add(ProjectilePlugin)
+            .add(AuraPlugin)
+            .add(NovaPlugin)
+            .add(StatusPlugin)
+            .add(ModifiersPlugin)
+            .add(PassivePlugin)
     }
 }
 
 pub struct SkillsPlugin;
 
+/// Systems of the skills that hit, run one skill after the other in this order and before
+/// the damage is taken. This keeps the order of their draws from the [`RunRng`] and of the
+/// hits they write the same from one run to the next.
+#[derive(SystemSet, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum CombatSystems {
+    Melee,
+    Aura,
+    Laser,
+    Projectile,
+    Nova,
+    /// Statuses are applied, and deal their damage over time.
+    Status,
+}
+
+/// Registers every skill component, either with the [`App`], which also adds the systems
+/// applying their specs, or with a bare [`SkillRegistry`], e.g. for validating assets.
+pub fn register_skill_components<R: RegisterSkillComponent>(registrar: &mut R) -> &mut R {
+    registrar
+        .register_skill_component::<MaxHealth>()
+        .register_skill_component::<HealthRegen>()
+        .register_skill_component::<Resistances>()
+        .register_skill_component::<CriticalHits>()
+        .register_skill_component::<HealOnKill>()
+        .register_skill_component::<Swarm>()
+        .register_skill_component::<XpGather>()
+        .register_skill_component::<Melee>()
+        .register_skill_component::<Laser>()
+        .register_skill_component::<Projectile>()
+        .register_skill_component::<Aura>()
+        .register_skill_component::<Nova>()
+}
+
 impl Plugin for SkillsPlugin {
     fn build(&self, app: &mut App) {
+        register_skill_components(app);
         app.register_type::<Skill>()
             .register_type::<Attribute>()
             .register_type::<Value>()
+            .init_resource::<SkillRegistry>()
             .init_asset::<SkillsAsset>()
             .init_asset_loader::<SkillsAssetLoader>()
             .init_resource::<Skills>()
             .init_resource::<SkillUpgradeOptions>()
+            .configure_sets(
+                Update,
+                (
+                    CombatSystems::Melee,
+                    CombatSystems::Aura,
+                    CombatSystems::Laser,
+                    CombatSystems::Projectile,
+                    CombatSystems::Nova,
+                    CombatSystems::Status,
+                )
+                    .chain(),
+            )
             .add_systems(Startup, setup_skills_asset_handle)
             .add_systems(Update, skills_asset_on_load)
-            .add_systems(Update, init_upgrade_options.run_if(in_state(AppState::Run)))
+            .add_systems(
+                Update,
+                (
+                    init_upgrade_options,
+                    dequeue_skill_specs,
+                    record_equipped_skills.before(die),
+                )
+                    .run_if(in_state(AppState::Run)),
+            )
             .add_systems(
                 Update,
                 apply_upgrade_selection.run_if(in_state(AppState::Upgrade)),
@@ -62,35 +146,93 @@ impl Plugin for SkillsPlugin {
     }
 }
 
-#[derive(Copy, Clone, Reflect, Debug, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Reflect, Debug, Deserialize)]
 pub enum Value {
     F(f32),
     U(u32),
     AddF(f32),
     AddU(u32),
     Perc(f32),
+    Mul(f32),
+    /// Raises the field to at least the value.
+    Min(f32),
+    /// Caps the field at the value.
+    Max(f32),
+    /// Sets the field to a factor of an attribute of another skill of the same entity,
+    /// e.g. `Ref(Health, MaxHp, 0.02)` for 2% of the max hp.
+    Ref(Skill, Attribute, f32),
+    /// Adds a factor of an attribute of another skill of the same entity.
+    AddRef(Skill, Attribute, f32),
+    /// Adds a factor of the player level.
+    PerLevel(f32),
 }
 
 impl Value {
+    /// Change from `other`, the value of the previous upgrade level, to this one.
     pub fn delta(&self, other: Value) -> Option<Value> {
         match self {
             Value::F(v1) => {
                 if let Value::F(v2) = other {
                     Some(Value::AddF(v1 - v2))
                 } else {
-                    None
+                    Some(*self)
                 }
             }
             Value::U(v1) => {
                 if let Value::U(v2) = other {
                     Some(Value::AddF((v1 - v2) as f32))
                 } else {
-                    None
+                    Some(*self)
                 }
             }
             Value::AddF(v1) => Some(Value::AddF(*v1)),
             Value::AddU(v1) => Some(Value::AddF(*v1 as f32)),
             Value::Perc(v1) => Some(Value::Perc(*v1)),
+            Value::Mul(_)
+            | Value::Min(_)
+            | Value::Max(_)
+            | Value::Ref(..)
+            | Value::AddRef(..)
+            | Value::PerLevel(_) => Some(*self),
+        }
+    }
+
+    /// Applies the value to a field, with integer values converted to `f32`. Values
+    /// referencing attributes or the level leave the field unchanged.
+    pub fn apply_to_f32(&self, field: f32) -> f32 {
+        match self {
+            Value::F(v) => *v,
+            Value::U(v) => *v as f32,
+            Value::AddF(v) => field + v,
+            Value::AddU(v) => field + *v as f32,
+            Value::Perc(v) => field + field * v / 100.,
+            Value::Mul(v) => field * v,
+            Value::Min(v) => field.max(*v),
+            Value::Max(v) => field.min(*v),
+            Value::Ref(..) | Value::AddRef(..) | Value::PerLevel(_) => field,
+        }
+    }
+
+    /// Evaluates values referencing attributes or the level of the entity to plain values.
+    /// Returns `None` while a referenced attribute has no value yet.
+    pub fn resolve(&self, values: &AttributeValues, level: u32) -> Option<Value> {
+        match *self {
+            Value::Ref(skill, attr, factor) => {
+                values.get(skill, attr).map(|v| Value::F(v * factor))
+            }
+            Value::AddRef(skill, attr, factor) => {
+                values.get(skill, attr).map(|v| Value::AddF(v * factor))
+            }
+            Value::PerLevel(factor) => Some(Value::AddF(factor * level as f32)),
+            value => Some(value),
+        }
+    }
+
+    /// Type of the component field this value can be applied to.
+    pub fn field_type_id(&self) -> TypeId {
+        match self {
+            Value::U(_) | Value::AddU(_) => TypeId::of::<u32>(),
+            _ => TypeId::of::<f32>(),
         }
     }
 
@@ -101,6 +243,25 @@ impl Value {
             Value::AddF(v) => v.abs() < 0.01,
             Value::AddU(v) => *v == 0,
             Value::Perc(v) => v.abs() < 0.01,
+            Value::Mul(v) => (v - 1.).abs() < 0.01,
+            Value::Min(_) | Value::Max(_) | Value::Ref(..) => false,
+            Value::AddRef(_, _, v) | Value::PerLevel(v) => v.abs() < 0.01,
+        }
+    }
+
+    /// The value as text, with `attr_name` naming referenced attributes.
+    fn ui_text(&self, attr_name: impl Fn(Attribute) -> String) -> String {
+        match self {
+            Value::Ref(_, attr, factor) => {
+                format!("{}% of {}", Value::round(factor * 100.), attr_name(*attr))
+            }
+            Value::AddRef(_, attr, factor) => format!(
+                "{}{}% of {}",
+                Value::sign(*factor),
+                Value::round(factor * 100.),
+                attr_name(*attr)
+            ),
+            value => value.to_string(),
         }
     }
 
@@ -121,36 +282,17 @@ impl fmt::Display for Value {
             Value::AddF(v1) => write!(f, "{}{}", Value::sign(*v1), Value::round(*v1)),
             Value::AddU(v1) => write!(f, "{}{}", Value::sign(*v1 as f32), v1),
             Value::Perc(v1) => write!(f, "{}{}%", Value::sign(*v1), Value::round(*v1)),
+            Value::Mul(v1) => write!(f, "x{}", Value::round(*v1)),
+            Value::Min(v1) => write!(f, "min {}", Value::round(*v1)),
+            Value::Max(v1) => write!(f, "max {}", Value::round(*v1)),
+            Value::Ref(..) | Value::AddRef(..) => {
+                f.write_str(&self.ui_text(|attr| attr.to_string()))
+            }
+            Value::PerLevel(v1) => write!(f, "{}{}/level", Value::sign(*v1), Value::round(*v1)),
         }
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Reflect, Debug, Deserialize, Hash)]
-pub enum Attribute {
-    MaxHp,
-    HpPerSec,
-    Range,
-    Acceleration,
-    Dps,
-    Duration,
-    Cooldown,
-    Speed,
-}
-
-#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Reflect, Debug, Deserialize, Hash)]
-pub enum Skill {
-    Health,
-    HealthRegen,
-    XpGather,
-    Swarm,
-    Melee,
-    Laser,
-}
-
-pub trait IsSkill {
-    fn skill() -> Skill;
-}
-
 // skill component initialization
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Debug, Reflect, Deserialize)]
@@ -177,7 +319,7 @@ impl Level {
         }
     }
 
-    pub fn index<'a, T>(&'a self, list: &'a Vec<T>) -> Option<&'a T> {
+    pub fn index<'a, T>(&'a self, list: &'a [T]) -> Option<&'a T> {
         list.get(self.0 as usize)
     }
 }
@@ -193,10 +335,54 @@ pub type SkillSpec = HashMap<Attribute, Value>;
 #[derive(Component, Default)]
 pub struct SkillSpecs(pub HashMap<Skill, (Level, SkillSpec)>);
 
+/// Skills to unequip, like those a boss stops using in a new phase. The component of a
+/// removed skill is removed too once none of the skills bound to it is left, or starts
+/// over from its default when [`SkillSpecs`] of one of them are waiting.
+#[derive(Component, Default)]
+pub struct RemovedSkills(pub HashSet<Skill>);
+
+/// Specs of upgrades taken in a row, moved into [`SkillSpecs`] in order, as soon as the
+/// previous spec of the same skill was applied.
+#[derive(Component, Default)]
+pub struct QueuedSkillSpecs(VecDeque<(Skill, Level, SkillSpec)>);
+
+impl QueuedSkillSpecs {
+    pub fn push(&mut self, skill: Skill, level: Level, spec: SkillSpec) {
+        self.0.push_back((skill, level, spec));
+    }
+}
+
+fn dequeue_skill_specs(
+    mut q_queued: Query<(Entity, &mut QueuedSkillSpecs, Option<&mut SkillSpecs>)>,
+    mut cmd: Commands,
+) {
+    for (entity, mut queued, specs) in &mut q_queued {
+        if queued.0.is_empty() {
+            continue;
+        }
+        let mut new_specs = SkillSpecs::default();
+        let specs = specs.map_or(&mut new_specs, Mut::into_inner);
+        for (skill, level, spec) in std::mem::take(&mut queued.0) {
+            if specs.0.contains_key(&skill) || queued.0.iter().any(|(s, ..)| *s == skill) {
+                queued.0.push_back((skill, level, spec));
+            } else {
+                specs.0.insert(skill, (level, spec));
+            }
+        }
+        if !new_specs.0.is_empty() {
+            cmd.entity(entity).insert(new_specs);
+        }
+    }
+}
+
 #[derive(Component, Clone, Default)]
+#[require(SkillModifiers, AttributeValues)]
 pub struct EquippedSkills {
     equipped: HashMap<Skill, Level>,
     selected: HashSet<Skill>,
+    passives: HashMap<Passive, Level>,
+    /// Skills replaced by their evolutions, never offered again.
+    evolved: HashSet<Skill>,
 }
 
 impl EquippedSkills {
@@ -208,6 +394,8 @@ impl EquippedSkills {
         Self {
             equipped: HashMap::new(),
             selected,
+            passives: HashMap::new(),
+            evolved: HashSet::new(),
         }
     }
 
@@ -215,40 +403,178 @@ impl EquippedSkills {
         self.equipped.contains_key(&skill)
     }
 
+    pub fn passives(&self) -> impl Iterator<Item = (&Passive, &Level)> {
+        self.passives.iter()
+    }
+
+    /// Equipped skills and passives with their levels.
+    pub fn upgrades(&self) -> impl Iterator<Item = (Upgrade, Level)> + '_ {
+        let skills = self
+            .equipped
+            .iter()
+            .map(|(skill, level)| (Upgrade::Skill(*skill), *level));
+        let passives = self
+            .passives
+            .iter()
+            .map(|(passive, level)| (Upgrade::Passive(*passive), *level));
+        skills.chain(passives)
+    }
+
+    /// Equips `skill` at `level`, unless it is already at a higher one, like while the
+    /// specs of queued levels are still applied, or was replaced by an evolution.
     fn set_level(&mut self, skill: Skill, level: Level, is_selected: bool) {
-        self.equipped.insert(skill, level);
+        if self.evolved.contains(&skill) {
+            return;
+        }
+        let equipped = self.equipped.entry(skill).or_insert(level);
+        *equipped = (*equipped).max(level);
         if is_selected {
             self.selected.insert(skill);
         }
     }
+
+    /// Removes `skill`, which may be equipped again later.
+    fn unequip(&mut self, skill: Skill) {
+        self.equipped.remove(&skill);
+        self.selected.remove(&skill);
+    }
+
+    /// Replaces skill `from` with its evolution `to`, at the first level.
+    fn evolve(&mut self, from: Skill, to: Skill) {
+        self.equipped.remove(&from);
+        self.selected.remove(&from);
+        self.evolved.insert(from);
+        self.set_level(to, Level::default(), true);
+    }
+}
+
+/// Applies a [resolved](Value::resolve) `value` to a reflected component field of a
+/// matching type.
+pub(crate) fn apply_value(fld: &mut dyn PartialReflect, value: Value) {
+    match value {
+        Value::F(v) => fld.try_downcast_mut::<f32>().map(|f| *f = v),
+        Value::U(v) => fld.try_downcast_mut::<u32>().map(|f| *f = v),
+        Value::AddF(v) => fld.try_downcast_mut::<f32>().map(|f| *f += v),
+        Value::AddU(v) => fld.try_downcast_mut::<u32>().map(|f| *f += v),
+        Value::Perc(v) => fld.try_downcast_mut::<f32>().map(|f| *f += *f * v / 100.),
+        Value::Mul(v) => fld.try_downcast_mut::<f32>().map(|f| *f *= v),
+        Value::Min(v) => fld.try_downcast_mut::<f32>().map(|f| *f = f.max(v)),
+        Value::Max(v) => fld.try_downcast_mut::<f32>().map(|f| *f = f.min(v)),
+        Value::Ref(..) | Value::AddRef(..) | Value::PerLevel(_) => {
+            error!("Unresolved value {value:?}!");
+            None
+        }
+    };
+}
+
+/// Rotation of ground circles drawn with gizmos, which are in the XY plane by default.
+pub(crate) fn ground_circle_isometry(pos: Vec3) -> Isometry3d {
+    Isometry3d::new(
+        Vec3::new(pos.x, 0.05, pos.z),
+        Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+    )
+}
+
+/// Systems applying [`RemovedSkills`] and [`SkillSpecs`] to the skill components, one
+/// pair per [registered](RegisterSkillComponent) component.
+#[derive(SystemSet, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ApplySkillSpecs;
+
+/// Unequips the [`RemovedSkills`] bound to `T`.
+pub fn remove_skills<T: Component<Mutability = Mutable> + Struct + Default>(
+    skills_meta: Res<Skills>,
+    mut q_removed: Query<(Entity, &mut RemovedSkills, &mut EquippedSkills)>,
+    mut q_skill: Query<(&mut SkillBase<T>, Option<&SkillSpecs>)>,
+    mut cmd: Commands,
+) {
+    for (entity, mut removed, mut equipped) in &mut q_removed {
+        let bound = skills_meta.bound_skills::<T>().collect::<Vec<_>>();
+        let to_remove = bound
+            .iter()
+            .filter(|skill| removed.0.remove(*skill))
+            .copied()
+            .collect::<Vec<_>>();
+        if to_remove.is_empty() {
+            continue;
+        }
+        if removed.0.is_empty() {
+            cmd.entity(entity).remove::<RemovedSkills>();
+        }
+        for skill in to_remove {
+            equipped.unequip(skill);
+        }
+        let Ok((mut base, specs)) = q_skill.get_mut(entity) else {
+            continue;
+        };
+        if bound.iter().any(|skill| equipped.is_equipped(*skill)) {
+            continue;
+        }
+        if specs.is_some_and(|specs| bound.iter().any(|skill| specs.0.contains_key(skill))) {
+            base.0 = T::default();
+        } else {
+            cmd.entity(entity).remove::<(T, SkillBase<T>)>();
+        }
+    }
 }
 
-pub fn apply_skill_specs<T: Component<Mutability = Mutable> + Struct + Default + IsSkill>(
+/// Applies the specs of the skills bound to `T` to its [`SkillBase`], from which
+/// [`apply_skill_modifiers`](modifiers::apply_skill_modifiers) computes `T`.
+///
+/// Values referencing other attributes are evaluated once, when the spec is applied. A
+/// spec waits until the attributes it references have values, while attributes of skills
+/// the entity doesn't have count as 0.
+pub fn apply_skill_specs<T: Component<Mutability = Mutable> + Struct + Default>(
     skills_meta: Res<Skills>,
-    q_no_skill: Query<(Entity, &SkillSpecs), Without<T>>,
-    mut q_skill: Query<(Entity, &mut T, &mut EquippedSkills, &mut SkillSpecs)>,
+    q_no_skill: Query<(Entity, &SkillSpecs), Without<SkillBase<T>>>,
+    mut q_skill: Query<(
+        Entity,
+        &mut SkillBase<T>,
+        &mut EquippedSkills,
+        &mut SkillSpecs,
+    )>,
+    q_values: Query<&AttributeValues>,
+    q_level: Query<&XpGatherState>,
     mut cmd: Commands,
 ) {
-    let skill = T::skill();
     for (entity, specs) in &q_no_skill {
-        if specs.0.contains_key(&skill) {
-            cmd.entity(entity).insert(T::default());
+        if skills_meta
+            .bound_skills::<T>()
+            .any(|skill| specs.0.contains_key(&skill))
+        {
+            cmd.entity(entity)
+                .insert((T::default(), SkillBase(T::default())));
         }
     }
-    for (entity, mut refl_struct, mut equipped, mut specs) in &mut q_skill {
-        if let Some((level, spec)) = specs.0.get(&skill) {
-            for (attr, val) in spec {
+    for (entity, mut base, mut equipped, mut specs) in &mut q_skill {
+        let player_level = q_level
+            .get(entity)
+            .map_or(1, XpGatherState::get_player_level);
+        for skill in skills_meta.bound_skills::<T>() {
+            let Some((level, spec)) = specs.0.get(&skill) else {
+                continue;
+            };
+            let values = q_values.get(entity).ok();
+            let has_skill = |skill| equipped.is_equipped(skill) || specs.0.contains_key(&skill);
+            let Some(spec) = spec
+                .iter()
+                .map(|(attr, val)| {
+                    let val = values
+                        .and_then(|values| val.resolve(values, player_level))
+                        .or(match *val {
+                            Value::Ref(skill, ..) if !has_skill(skill) => Some(Value::F(0.)),
+                            Value::AddRef(skill, ..) if !has_skill(skill) => Some(Value::AddF(0.)),
+                            _ => None,
+                        })?;
+                    Some((*attr, val))
+                })
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            for (attr, val) in &spec {
                 if let Some(attr_meta) = skills_meta.attributes.get(attr) {
-                    if let Some(fld) = refl_struct.field_mut(&attr_meta.field_name) {
-                        match val {
-                            Value::F(v) => fld.try_downcast_mut::<f32>().map(|f| *f = *v),
-                            Value::U(v) => fld.try_downcast_mut::<u32>().map(|f| *f = *v),
-                            Value::AddF(v) => fld.try_downcast_mut::<f32>().map(|f| *f += *v),
-                            Value::AddU(v) => fld.try_downcast_mut::<u32>().map(|f| *f += *v),
-                            Value::Perc(v) => {
-                                fld.try_downcast_mut::<f32>().map(|f| *f += *f * *v / 100.)
-                            }
-                        };
+                    if let Some(fld) = base.0.field_mut(&attr_meta.field_name) {
+                        apply_value(fld, *val);
                     } else {
                         error!(
                             "Field {} not found for skill {skill:?}!",
@@ -263,104 +589,353 @@ pub fn apply_skill_specs<T: Component<Mutability = Mutable> + Struct + Default +
             equipped.set_level(skill, *level, false);
 
             specs.0.remove(&skill);
-            if specs.0.is_empty() {
-                cmd.entity(entity).remove::<SkillSpecs>();
-            }
+        }
+        if specs.0.is_empty() {
+            cmd.entity(entity).remove::<SkillSpecs>();
         }
     }
 }
 
 // skill upgrades
 
+/// Slots for selected skills and for passive items.
 #[derive(Component)]
-pub struct MaxUpgradableSkills(pub u8);
+#[require(UpgradeActions, QueuedSkillSpecs)]
+pub struct MaxUpgradableSkills {
+    pub skills: u8,
+    pub passives: u8,
+}
+
+/// Skill or passive item upgrade offered on level up.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Upgrade {
+    Skill(Skill),
+    Passive(Passive),
+}
+
+/// Most upgrades offered at once, see [`UpgradeOffers::count`].
+pub const MAX_UPGRADE_OFFERS: usize = 5;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default, Debug, Deserialize)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+}
+
+/// How upgrades are offered on level up.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct UpgradeOffers {
+    /// Number of upgrades offered at once, at most [`MAX_UPGRADE_OFFERS`].
+    pub count: usize,
+    /// Times per run the offers can be rerolled.
+    pub rerolls: u32,
+    /// Times per run a level up can be skipped without taking an upgrade.
+    pub skips: u32,
+    /// Times per run an offered skill can be banished from all later offers.
+    pub banishes: u32,
+    /// Relative chance of offering an upgrade of each rarity, 1 when missing.
+    pub weights: HashMap<Rarity, f32>,
+    /// Rarity of each upgrade level of a skill, common when missing.
+    pub rarities: HashMap<Skill, Vec<Rarity>>,
+}
+
+impl Default for UpgradeOffers {
+    fn default() -> Self {
+        Self {
+            count: 3,
+            rerolls: 0,
+            skips: 0,
+            banishes: 0,
+            weights: HashMap::new(),
+            rarities: HashMap::new(),
+        }
+    }
+}
+
+impl UpgradeOffers {
+    pub fn weight(&self, rarity: Rarity) -> f32 {
+        self.weights.get(&rarity).copied().unwrap_or(1.)
+    }
+}
+
+/// Skill offered in place of skill `from` once `from` reaches its max level while skill
+/// `with` is equipped. The evolved skill is bound to the same component as `from`, so
+/// its levels apply on top of the values `from` reached.
+///
+/// Written as a `(from, with)` tuple in RON, which can't read bare ids as field values.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(from = "(Skill, Skill)")]
+pub struct Evolution {
+    pub from: Skill,
+    pub with: Skill,
+}
 
+impl From<(Skill, Skill)> for Evolution {
+    fn from((from, with): (Skill, Skill)) -> Self {
+        Self { from, with }
+    }
+}
+
+/// What the player does with the offered upgrades.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum UpgradeChoice {
+    /// Takes the upgrade at the index into [`SkillUpgradeOptions::offers`].
+    Take(usize),
+    /// Replaces the offered upgrades with new ones.
+    Reroll,
+    /// Levels up without taking an upgrade.
+    Skip,
+    /// Removes the skill or passive item of the upgrade at the index from all later
+    /// offers, and rerolls.
+    Banish(usize),
+}
+
+/// Rerolls, skips and banishes the player used this run, limited by [`UpgradeOffers`].
+#[derive(Component, Default)]
+pub struct UpgradeActions {
+    pub rerolls: u32,
+    pub skips: u32,
+    pub banishes: u32,
+    pub banished: HashSet<Upgrade>,
+}
+
+impl UpgradeActions {
+    /// Uses left of the action of `choice`, or `None` for [`UpgradeChoice::Take`].
+    pub fn left(&self, offers: &UpgradeOffers, choice: UpgradeChoice) -> Option<u32> {
+        let (max, used) = match choice {
+            UpgradeChoice::Take(_) => return None,
+            UpgradeChoice::Reroll => (offers.rerolls, self.rerolls),
+            UpgradeChoice::Skip => (offers.skips, self.skips),
+            UpgradeChoice::Banish(_) => (offers.banishes, self.banishes),
+        };
+        Some(max.saturating_sub(used))
+    }
+}
+
+/// Upgrades offered on level up, picked through [`PlayerIntent::upgrade`].
 #[derive(Resource, Default)]
 pub struct SkillUpgradeOptions {
     pub entity: Option<Entity>,
-    pub skills: Vec<(Skill, Level)>,
-    pub selected: Option<(Skill, Level)>,
+    pub offers: Vec<(Upgrade, Level)>,
+    /// Level ups waiting for an upgrade, including the offered one. They are all taken
+    /// before the run resumes.
+    pub pending: u32,
+}
+
+/// Draws [`UpgradeOffers::count`] upgrades among the next levels of the equipped skills
+/// and passive items, the first levels of new ones and the unlocked [`Evolution`]s,
+/// weighted by their rarity.
+fn roll_upgrade_options(
+    skills: &Skills,
+    equipped: &EquippedSkills,
+    max_skills: &MaxUpgradableSkills,
+    actions: &UpgradeActions,
+    rng: &mut StdRng,
+) -> Vec<(Upgrade, Level)> {
+    let mut upgrades = vec![];
+    for skill in &equipped.selected {
+        if let Some(levels) = skills.upgrades.get(skill)
+            && let Some(level) = equipped.equipped.get(skill)
+            && let Some(next_level) = level.next(levels.len())
+        {
+            upgrades.push((Upgrade::Skill(*skill), next_level));
+        }
+    }
+    if equipped.selected.len() < max_skills.skills as usize {
+        for skill in skills.upgrades.keys() {
+            if !equipped.selected.contains(skill)
+                && !equipped.evolved.contains(skill)
+                && !skills.evolutions.contains_key(skill)
+            {
+                upgrades.push((Upgrade::Skill(*skill), Level::default()));
+            }
+        }
+    }
+    for (skill, evolution) in &skills.evolutions {
+        if skills.is_max_level(evolution.from, equipped.equipped.get(&evolution.from))
+            && equipped.is_equipped(evolution.with)
+        {
+            upgrades.push((Upgrade::Skill(*skill), Level::default()));
+        }
+    }
+    for (passive, passive_meta) in &skills.passives {
+        if let Some(level) = equipped.passives.get(passive) {
+            if let Some(next_level) = level.next(passive_meta.levels.len()) {
+                upgrades.push((Upgrade::Passive(*passive), next_level));
+            }
+        } else if equipped.passives.len() < max_skills.passives as usize {
+            upgrades.push((Upgrade::Passive(*passive), Level::default()));
+        }
+    }
+    upgrades.retain(|(upgrade, level)| {
+        !actions.banished.contains(upgrade)
+            && skills.offers.weight(skills.rarity(*upgrade, *level)) > 0.
+    });
+    upgrades
+        .choose_multiple_weighted(rng, skills.offers.count, |(upgrade, level)| {
+            skills.offers.weight(skills.rarity(*upgrade, *level))
+        })
+        .map(|offers| offers.copied().collect())
+        .unwrap_or_default()
 }
 
 fn init_upgrade_options(
     skills: Res<Skills>,
     mut next_state: ResMut<NextState<AppState>>,
     mut upgrades: ResMut<SkillUpgradeOptions>,
+    mut intent: ResMut<PlayerIntent>,
+    mut rng: ResMut<RunRng>,
     q_xp_gather_state: Query<(
         Entity,
         &XpGatherState,
         &MaxUpgradableSkills,
         &EquippedSkills,
+        &UpgradeActions,
     )>,
 ) {
-    for (entity, xp_gather_state, max_skills, equipped) in &q_xp_gather_state {
+    for (entity, xp_gather_state, max_skills, equipped, actions) in &q_xp_gather_state {
         if xp_gather_state.get_gather_level() > xp_gather_state.get_player_level() {
-            let mut skill_upgrades = vec![];
-            for skill in &equipped.selected {
-                if let Some(levels) = skills.upgrades.get(skill) {
-                    if let Some(level) = equipped.equipped.get(skill) {
-                        if let Some(next_level) = level.next(levels.len()) {
-                            skill_upgrades.push((*skill, next_level));
-                        }
-                    }
-                }
-            }
-            if equipped.selected.len() < max_skills.0 as usize {
-                for skill in skills.upgrades.keys() {
-                    if !equipped.selected.contains(skill) {
-                        skill_upgrades.push((*skill, Level::default()));
-                    }
-                }
-            }
-            upgrades.skills.clear();
-            let mut rng = thread_rng();
-            upgrades.skills = skill_upgrades
-                .choose_multiple(&mut rng, 3)
-                .map(|s| s.clone())
-                .collect();
-            if !upgrades.skills.is_empty() {
+            upgrades.offers =
+                roll_upgrade_options(&skills, equipped, max_skills, actions, &mut rng.upgrades);
+            if !upgrades.offers.is_empty() {
                 upgrades.entity = Some(entity);
-                upgrades.selected = None;
+                upgrades.pending = xp_gather_state.pending_levels();
+                intent.upgrade = None;
                 next_state.set(AppState::Upgrade);
             }
         } else {
             upgrades.entity = None;
-            upgrades.skills.clear();
-            upgrades.selected = None;
+            upgrades.offers.clear();
         }
     }
 }
 
-fn apply_upgrade_selection(
+/// Keeps the [`RunStats::equipped`] up to date with the skills of the player, from its
+/// spawn until it dies.
+fn record_equipped_skills(
+    q_player: Query<&EquippedSkills, (With<Player>, Changed<EquippedSkills>)>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    if let Ok(equipped) = q_player.single() {
+        run_stats.equipped = equipped.upgrades().collect();
+    }
+}
+
+pub(crate) fn apply_upgrade_selection(
     mut next_state: ResMut<NextState<AppState>>,
     mut upgrades: ResMut<SkillUpgradeOptions>,
+    mut intent: ResMut<PlayerIntent>,
     skills: Res<Skills>,
-    mut q_xp_gather_state: Query<&mut XpGatherState>,
-    mut q_equipped_skills: Query<&mut EquippedSkills>,
-    mut cmd: Commands,
+    mut rng: ResMut<RunRng>,
+    mut run_stats: ResMut<RunStats>,
+    mut q_player: Query<(
+        &mut XpGatherState,
+        &mut EquippedSkills,
+        &MaxUpgradableSkills,
+        &mut UpgradeActions,
+        &mut SkillModifiers,
+        &mut QueuedSkillSpecs,
+    )>,
 ) {
-    let (Some(entity), Some((skill, level))) = (upgrades.entity, upgrades.selected) else {
+    let (Some(entity), Some(choice)) = (upgrades.entity, intent.upgrade.take()) else {
         return;
     };
-    if let Ok(mut xp_gather_state) = q_xp_gather_state.get_mut(entity) {
-        xp_gather_state.upgrade_player_level();
+    let Ok((
+        mut xp_gather_state,
+        mut equipped_skills,
+        max_skills,
+        mut actions,
+        mut modifiers,
+        mut queued_specs,
+    )) = q_player.get_mut(entity)
+    else {
+        return;
+    };
+    if actions.left(&skills.offers, choice) == Some(0) {
+        return;
     }
-    if let Ok(mut equipped_skills) = q_equipped_skills.get_mut(entity) {
-        equipped_skills.set_level(skill, level, true);
+    match choice {
+        UpgradeChoice::Take(idx) => match upgrades.offers.get(idx).copied() {
+            Some((Upgrade::Skill(skill), level)) => {
+                xp_gather_state.upgrade_player_level();
+                if let Some(evolution) = skills.evolutions.get(&skill) {
+                    equipped_skills.evolve(evolution.from, skill);
+                } else {
+                    equipped_skills.set_level(skill, level, true);
+                }
+                if let Some(levels) = skills.upgrades.get(&skill) {
+                    if let Some(spec) = level.index(levels) {
+                        queued_specs.0.push_back((skill, level, spec.clone()));
+                    } else {
+                        error!("Did not find level {level} upgrades for equipped skill {skill:?}.");
+                    }
+                } else {
+                    error!("Did not find upgrades for equipped skill {skill:?}.");
+                }
+            }
+            Some((Upgrade::Passive(passive), level)) => {
+                xp_gather_state.upgrade_player_level();
+                equipped_skills.passives.insert(passive, level);
+                if let Some(spec) = skills
+                    .passives
+                    .get(&passive)
+                    .and_then(|passive_meta| level.index(&passive_meta.levels))
+                {
+                    set_passive_modifiers(&mut modifiers, passive, spec);
+                } else {
+                    error!("Did not find level {level} of passive {passive}.");
+                }
+            }
+            None => return,
+        },
+        UpgradeChoice::Skip => {
+            actions.skips += 1;
+            xp_gather_state.upgrade_player_level();
+        }
+        UpgradeChoice::Reroll | UpgradeChoice::Banish(_) => {
+            if let UpgradeChoice::Banish(idx) = choice {
+                let Some((upgrade, _)) = upgrades.offers.get(idx).copied() else {
+                    return;
+                };
+                actions.banishes += 1;
+                actions.banished.insert(upgrade);
+            } else {
+                actions.rerolls += 1;
+            }
+            upgrades.offers = roll_upgrade_options(
+                &skills,
+                &equipped_skills,
+                max_skills,
+                &actions,
+                &mut rng.upgrades,
+            );
+            // with nothing left to offer, the level up waits like when all skills are maxed
+            if !upgrades.offers.is_empty() {
+                return;
+            }
+        }
     }
-    if let Some(levels) = skills.upgrades.get(&skill) {
-        if let Some(spec) = level.index(levels) {
-            cmd.entity(entity)
-                .insert(SkillSpecs(HashMap::from([(skill, (level, spec.clone()))])));
-        } else {
-            error!("Did not find level {level} upgrades for equipped skill {skill:?}.");
+    run_stats.level = xp_gather_state.get_player_level();
+    // the next queued level up is offered right away, without resuming the run
+    if xp_gather_state.pending_levels() > 0 {
+        upgrades.offers = roll_upgrade_options(
+            &skills,
+            &equipped_skills,
+            max_skills,
+            &actions,
+            &mut rng.upgrades,
+        );
+        if !upgrades.offers.is_empty() {
+            upgrades.pending = xp_gather_state.pending_levels();
+            return;
         }
-    } else {
-        error!("Did not find upgrades for equipped skill {skill:?}.");
     }
     upgrades.entity = None;
-    upgrades.skills.clear();
-    upgrades.selected = None;
+    upgrades.offers.clear();
     next_state.set(AppState::Run);
 }
 
@@ -368,13 +943,292 @@ fn apply_upgrade_selection(
 
 #[derive(Asset, TypePath, Debug, Deserialize)]
 pub struct SkillsAsset {
-    pub skills: HashMap<Skill, String>,
+    pub skills: HashMap<Skill, SkillMeta>,
     pub attributes: HashMap<Attribute, AttributeMeta>,
     pub upgrades: HashMap<Skill, Vec<SkillSpec>>,
+    #[serde(default)]
+    pub statuses: HashMap<Status, StatusMeta>,
+    #[serde(default)]
+    pub offers: UpgradeOffers,
+    /// Evolved skills, by the skill they are offered as.
+    #[serde(default)]
+    pub evolutions: HashMap<Skill, Evolution>,
+    #[serde(default)]
+    pub passives: HashMap<Passive, PassiveMeta>,
+    #[serde(default)]
+    pub xp_curve: XpCurve,
+    /// Permanent starting bonuses bought with currency between runs, by name.
+    #[serde(default)]
+    pub bonuses: HashMap<String, BonusMeta>,
+    #[serde(skip)]
+    pub hash: AssetHash,
+}
+
+impl SkillsAsset {
+    /// Checks that `spec` only uses declared attributes, that each attribute maps to a field
+    /// of the skill's component, and that each value fits the field's type.
+    pub fn validate_spec(
+        &self,
+        registry: &SkillRegistry,
+        skill: Skill,
+        spec: &SkillSpec,
+    ) -> Result<(), SkillSpecError> {
+        let Some(skill_meta) = self.skills.get(&skill) else {
+            return Err(SkillSpecError::UndeclaredSkill(skill));
+        };
+        let Some(component) = registry.get(&skill_meta.component) else {
+            return Err(SkillSpecError::UnregisteredComponent {
+                skill,
+                component: skill_meta.component.clone(),
+            });
+        };
+        for (attr, val) in spec {
+            let Some(attr_meta) = self.attributes.get(attr) else {
+                return Err(SkillSpecError::UndeclaredAttribute(*attr));
+            };
+            let Some(field) = component.info.field(&attr_meta.field_name) else {
+                return Err(SkillSpecError::FieldNotFound {
+                    attribute: *attr,
+                    field: attr_meta.field_name.clone(),
+                    component: skill_meta.component.clone(),
+                });
+            };
+            self.validate_ref(registry, val)?;
+            if field.type_id() != val.field_type_id() {
+                return Err(SkillSpecError::ValueTypeMismatch {
+                    attribute: *attr,
+                    value: *val,
+                    field: attr_meta.field_name.clone(),
+                    field_type: field.type_path(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the attributes of passive item `spec` are declared, and that each value
+    /// fits the matching field of at least one skill component.
+    fn validate_passive_spec(
+        &self,
+        registry: &SkillRegistry,
+        spec: &SkillSpec,
+    ) -> Result<(), SkillSpecError> {
+        for (attr, val) in spec {
+            let Some(attr_meta) = self.attributes.get(attr) else {
+                return Err(SkillSpecError::UndeclaredAttribute(*attr));
+            };
+            self.validate_ref(registry, val)?;
+            if !self
+                .skills
+                .values()
+                .filter_map(|skill_meta| registry.get(&skill_meta.component))
+                .filter_map(|component| component.info.field(&attr_meta.field_name))
+                .any(|field| field.type_id() == val.field_type_id())
+            {
+                return Err(SkillSpecError::NoFittingField {
+                    attribute: *attr,
+                    value: *val,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the skill and attribute referenced by `val` are declared, and that the
+    /// attribute maps to a field of the skill's component, so that it gets a value.
+    fn validate_ref(&self, registry: &SkillRegistry, val: &Value) -> Result<(), SkillSpecError> {
+        if let Value::Ref(ref_skill, ref_attr, _) | Value::AddRef(ref_skill, ref_attr, _) = *val {
+            let Some(skill_meta) = self.skills.get(&ref_skill) else {
+                return Err(SkillSpecError::UndeclaredSkill(ref_skill));
+            };
+            let Some(attr_meta) = self.attributes.get(&ref_attr) else {
+                return Err(SkillSpecError::UndeclaredAttribute(ref_attr));
+            };
+            if let Some(component) = registry.get(&skill_meta.component)
+                && component.info.field(&attr_meta.field_name).is_none()
+            {
+                return Err(SkillSpecError::FieldNotFound {
+                    attribute: ref_attr,
+                    field: attr_meta.field_name.clone(),
+                    component: skill_meta.component.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self, registry: &SkillRegistry) -> Result<(), SkillsAssetLoaderError> {
+        for (skill, skill_meta) in &self.skills {
+            if registry.get(&skill_meta.component).is_none() {
+                return Err(SkillSpecError::UnregisteredComponent {
+                    skill: *skill,
+                    component: skill_meta.component.clone(),
+                }
+                .into());
+            }
+            if let Some(status) = skill_meta
+                .inflicts
+                .filter(|status| !self.statuses.contains_key(status))
+            {
+                return Err(SkillsAssetLoaderError::UndeclaredStatus {
+                    skill: *skill,
+                    status,
+                });
+            }
+        }
+        for (status, status_meta) in &self.statuses {
+            if let Stacking::Stack(0) = status_meta.stacking {
+                return Err(SkillsAssetLoaderError::ZeroMaxStacks(*status));
+            }
+            for effect in &status_meta.effects {
+                match *effect {
+                    StatusEffect::Speed(multiplier) if multiplier < 0. => {
+                        return Err(SkillsAssetLoaderError::NegativeSpeedMultiplier {
+                            status: *status,
+                            multiplier,
+                        });
+                    }
+                    StatusEffect::Modify(attribute, _)
+                        if !self.attributes.contains_key(&attribute) =>
+                    {
+                        return Err(SkillsAssetLoaderError::UndeclaredStatusAttribute {
+                            status: *status,
+                            attribute,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if !self.xp_curve.is_valid() {
+            return Err(SkillsAssetLoaderError::InvalidXpCurve(
+                self.xp_curve.clone(),
+            ));
+        }
+        if !(1..=MAX_UPGRADE_OFFERS).contains(&self.offers.count) {
+            return Err(SkillsAssetLoaderError::InvalidOfferCount(self.offers.count));
+        }
+        for (rarity, weight) in &self.offers.weights {
+            if !weight.is_finite() || *weight < 0. {
+                return Err(SkillsAssetLoaderError::InvalidRarityWeight {
+                    rarity: *rarity,
+                    weight: *weight,
+                });
+            }
+        }
+        for (skill, rarities) in &self.offers.rarities {
+            if rarities.len() > self.upgrades.get(skill).map_or(0, Vec::len) {
+                return Err(SkillsAssetLoaderError::ExtraRarities(*skill));
+            }
+        }
+        for (skill, evolution) in &self.evolutions {
+            for required in [*skill, evolution.from, evolution.with] {
+                if !self.upgrades.contains_key(&required) {
+                    return Err(SkillsAssetLoaderError::InvalidEvolution {
+                        skill: *skill,
+                        reason: format!("{required} has no upgrades"),
+                    });
+                }
+            }
+            if evolution.from == evolution.with {
+                return Err(SkillsAssetLoaderError::InvalidEvolution {
+                    skill: *skill,
+                    reason: format!("{} can not evolve with itself", evolution.from),
+                });
+            }
+            let component = |skill| self.skills.get(skill).map(|meta| &meta.component);
+            if component(skill) != component(&evolution.from) {
+                return Err(SkillsAssetLoaderError::InvalidEvolution {
+                    skill: *skill,
+                    reason: format!("component differs from the one of {}", evolution.from),
+                });
+            }
+        }
+        for (passive, passive_meta) in &self.passives {
+            if passive_meta.levels.is_empty() {
+                return Err(SkillsAssetLoaderError::EmptyPassiveLevels(*passive));
+            }
+            if passive_meta.rarities.len() > passive_meta.levels.len() {
+                return Err(SkillsAssetLoaderError::ExtraPassiveRarities(*passive));
+            }
+            for (idx, spec) in passive_meta.levels.iter().enumerate() {
+                self.validate_passive_spec(registry, spec)
+                    .map_err(|error| SkillsAssetLoaderError::InvalidPassive {
+                        passive: *passive,
+                        level: Level(idx as u8),
+                        error,
+                    })?;
+            }
+        }
+        for (bonus, bonus_meta) in &self.bonuses {
+            if bonus_meta.costs.is_empty() {
+                return Err(SkillsAssetLoaderError::EmptyBonusCosts(bonus.clone()));
+            }
+            for (skill, spec) in &bonus_meta.skills {
+                self.validate_spec(registry, *skill, spec)
+                    .map_err(|error| SkillsAssetLoaderError::InvalidBonus {
+                        bonus: bonus.clone(),
+                        error,
+                    })?;
+            }
+        }
+        for (skill, levels) in &self.upgrades {
+            if levels.is_empty() {
+                return Err(SkillsAssetLoaderError::EmptyUpgrades(*skill));
+            }
+            for (idx, spec) in levels.iter().enumerate() {
+                self.validate_spec(registry, *skill, spec)
+                    .map_err(|error| SkillsAssetLoaderError::InvalidUpgrade {
+                        skill: *skill,
+                        level: Level(idx as u8),
+                        error,
+                    })?;
+            }
+        }
+        Ok(())
+    }
 }
 
-#[derive(Default)]
-pub struct SkillsAssetLoader;
+#[derive(Debug, Error)]
+pub enum SkillSpecError {
+    #[error("skill {0} is not declared in the skills asset")]
+    UndeclaredSkill(Skill),
+    #[error("component {component} of skill {skill} is not registered")]
+    UnregisteredComponent { skill: Skill, component: String },
+    #[error("attribute {0} is not declared in the skills asset")]
+    UndeclaredAttribute(Attribute),
+    #[error("field {field} of attribute {attribute} not found on component {component}")]
+    FieldNotFound {
+        attribute: Attribute,
+        field: String,
+        component: String,
+    },
+    #[error(
+        "value {value:?} of attribute {attribute} does not fit field {field} of type {field_type}"
+    )]
+    ValueTypeMismatch {
+        attribute: Attribute,
+        value: Value,
+        field: String,
+        field_type: &'static str,
+    },
+    #[error("value {value:?} of attribute {attribute} does not fit any skill component field")]
+    NoFittingField { attribute: Attribute, value: Value },
+}
+
+/// Loads `*.skills.ron` files and validates their specs against the registered skill
+/// components. A hot reload that fails validation keeps the previous asset.
+pub struct SkillsAssetLoader {
+    registry: SkillRegistry,
+}
+
+impl FromWorld for SkillsAssetLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            registry: world.get_resource_or_init::<SkillRegistry>().clone(),
+        }
+    }
+}
 
 #[non_exhaustive]
 #[derive(Debug, Error)]
@@ -383,6 +1237,54 @@ pub enum SkillsAssetLoaderError {
     Io(#[from] std::io::Error),
     #[error("Could not parse RON: {0}")]
     RonSpannedError(#[from] ron::error::SpannedError),
+    #[error("Invalid skill spec: {0}")]
+    InvalidSkillSpec(#[from] SkillSpecError),
+    #[error("No upgrade levels for skill {0}")]
+    EmptyUpgrades(Skill),
+    #[error("Invalid level {level} upgrade for skill {skill}: {error}")]
+    InvalidUpgrade {
+        skill: Skill,
+        level: Level,
+        error: SkillSpecError,
+    },
+    #[error("Skill {skill} inflicts undeclared status {status}")]
+    UndeclaredStatus { skill: Skill, status: Status },
+    #[error("Max stacks of status {0} is 0")]
+    ZeroMaxStacks(Status),
+    #[error("Negative speed multiplier {multiplier} of status {status}")]
+    NegativeSpeedMultiplier { status: Status, multiplier: f32 },
+    #[error("Status {status} modifies undeclared attribute {attribute}")]
+    UndeclaredStatusAttribute {
+        status: Status,
+        attribute: Attribute,
+    },
+    #[error("Upgrade offer count {0} is not between 1 and {MAX_UPGRADE_OFFERS}")]
+    InvalidOfferCount(usize),
+    #[error("Invalid weight {weight} of rarity {rarity:?}")]
+    InvalidRarityWeight { rarity: Rarity, weight: f32 },
+    #[error("More rarities than upgrade levels for skill {0}")]
+    ExtraRarities(Skill),
+    #[error("Invalid evolution {skill}: {reason}")]
+    InvalidEvolution { skill: Skill, reason: String },
+    #[error("XP curve {0:?} has a level needing no XP")]
+    InvalidXpCurve(XpCurve),
+    #[error("No levels for passive {0}")]
+    EmptyPassiveLevels(Passive),
+    #[error("More rarities than levels for passive {0}")]
+    ExtraPassiveRarities(Passive),
+    #[error("Invalid level {level} of passive {passive}: {error}")]
+    InvalidPassive {
+        passive: Passive,
+        level: Level,
+        error: SkillSpecError,
+    },
+    #[error("No costs for bonus {0:?}")]
+    EmptyBonusCosts(String),
+    #[error("Invalid bonus {bonus:?}: {error}")]
+    InvalidBonus {
+        bonus: String,
+        error: SkillSpecError,
+    },
 }
 
 impl AssetLoader for SkillsAssetLoader {
@@ -397,7 +1299,9 @@ impl AssetLoader for SkillsAssetLoader {
     ) -> Result<Self::Asset, Self::Error> {
         let mut bytes = Vec::new();
         reader.read_to_end(&mut bytes).await?;
-        let custom_asset = ron::de::from_bytes::<SkillsAsset>(&bytes)?;
+        let mut custom_asset = ron::de::from_bytes::<SkillsAsset>(&bytes)?;
+        custom_asset.hash = AssetHash::of(&bytes);
+        custom_asset.validate(&self.registry)?;
         Ok(custom_asset)
     }
 
@@ -406,6 +1310,27 @@ impl AssetLoader for SkillsAssetLoader {
     }
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct SkillMeta {
+    pub ui_name: String,
+    /// Short type name of the reflected component the skill specs are applied to,
+    /// as registered with [`RegisterSkillComponent::register_skill_component`].
+    pub component: String,
+    /// Status applied by the skill's hits, for the duration of its `status_duration` field.
+    #[serde(default)]
+    pub inflicts: Option<Status>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BonusMeta {
+    pub ui_name: String,
+    /// Currency cost of each level, the number of costs being the max level.
+    pub costs: Vec<u32>,
+    /// Specs added to the player's skills at the start of a run, once per level bought.
+    /// Skills the character does not start with are not affected.
+    pub skills: HashMap<Skill, SkillSpec>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct AttributeMeta {
     pub field_name: String,
@@ -417,12 +1342,150 @@ pub struct Skills {
     pub handle: Handle<SkillsAsset>,
     pub attributes: HashMap<Attribute, AttributeMeta>,
     pub attributes_inv: HashMap<String, Attribute>,
-    pub skills: HashMap<Skill, String>,
+    pub skills: HashMap<Skill, SkillMeta>,
+    pub components: HashMap<Skill, TypeId>,
     pub upgrades: HashMap<Skill, Vec<SkillSpec>>,
+    pub statuses: HashMap<Status, StatusMeta>,
+    pub offers: UpgradeOffers,
+    pub evolutions: HashMap<Skill, Evolution>,
+    pub passives: HashMap<Passive, PassiveMeta>,
+    pub xp_curve: XpCurve,
+    pub bonuses: HashMap<String, BonusMeta>,
 }
 
+impl Skills {
+    /// Skills whose specs are applied to component `T`.
+    pub fn bound_skills<T: 'static>(&self) -> impl Iterator<Item = Skill> + '_ {
+        let type_id = TypeId::of::<T>();
+        self.components
+            .iter()
+            .filter(move |(_, id)| **id == type_id)
+            .map(|(skill, _)| *skill)
+    }
+
+    /// Status inflicted by the skills bound to component `T`.
+    pub fn inflicted_status<T: 'static>(&self) -> Option<Status> {
+        self.bound_skills::<T>()
+            .find_map(|skill| self.skills.get(&skill)?.inflicts)
+    }
+
+    pub fn rarity(&self, upgrade: Upgrade, level: Level) -> Rarity {
+        match upgrade {
+            Upgrade::Skill(skill) => self.offers.rarities.get(&skill),
+            Upgrade::Passive(passive) => self
+                .passives
+                .get(&passive)
+                .map(|passive_meta| &passive_meta.rarities),
+        }
+        .and_then(|rarities| level.index(rarities))
+        .copied()
+        .unwrap_or_default()
+    }
+
+    /// Spec of `level` of `upgrade`: changes for a skill, total values for a passive item.
+    pub fn upgrade_spec(&self, upgrade: Upgrade, level: Level) -> Option<&SkillSpec> {
+        match upgrade {
+            Upgrade::Skill(skill) => self.upgrades.get(&skill)?.get(level.0 as usize),
+            Upgrade::Passive(passive) => self.passives.get(&passive)?.levels.get(level.0 as usize),
+        }
+    }
+
+    pub fn upgrade_ui_name(&self, upgrade: Upgrade) -> Option<&str> {
+        match upgrade {
+            Upgrade::Skill(skill) => self.ui_name(skill),
+            Upgrade::Passive(passive) => self
+                .passives
+                .get(&passive)
+                .map(|passive_meta| passive_meta.ui_name.as_str()),
+        }
+    }
+
+    /// Whether `level`, the equipped level of `skill` if any, is its last upgrade level.
+    pub fn is_max_level(&self, skill: Skill, level: Option<&Level>) -> bool {
+        level
+            .zip(self.upgrades.get(&skill))
+            .is_some_and(|(level, levels)| level.next(levels.len()).is_none())
+    }
+
+    /// What `skill` evolves from, or into, and with which other skill, e.g. for the
+    /// upgrade screen.
+    pub fn evolution_ui_text(&self, skill: Skill) -> Option<String> {
+        let name = |skill| self.ui_name(skill).unwrap_or_default();
+        if let Some(evolution) = self.evolutions.get(&skill) {
+            return Some(format!(
+                "Evolves max level {} with {}",
+                name(evolution.from),
+                name(evolution.with)
+            ));
+        }
+        self.evolutions
+            .iter()
+            .find(|(_, evolution)| evolution.from == skill)
+            .map(|(evolved, evolution)| {
+                format!(
+                    "Evolves into {} at max level with {}",
+                    name(*evolved),
+                    name(evolution.with)
+                )
+            })
+    }
+
+    pub fn ui_name(&self, skill: Skill) -> Option<&str> {
+        self.skills.get(&skill).map(|meta| meta.ui_name.as_str())
+    }
+
+    /// `value` as text, naming referenced attributes by their UI names.
+    pub fn value_ui_text(&self, value: &Value) -> String {
+        value.ui_text(|attr| {
+            self.attributes
+                .get(&attr)
+                .map_or_else(|| attr.to_string(), |attr_meta| attr_meta.ui_name.clone())
+        })
+    }
+
+    /// `spec` as a list of attribute UI names and values, sorted by name.
+    pub fn spec_ui_text(&self, spec: &SkillSpec) -> String {
+        let mut attrs = spec
+            .iter()
+            .filter_map(|(attr, val)| {
+                let attr_meta = self.attributes.get(attr)?;
+                Some(format!(
+                    "{}: {}",
+                    attr_meta.ui_name,
+                    self.value_ui_text(val)
+                ))
+            })
+            .collect::<Vec<_>>();
+        attrs.sort();
+        attrs.join(", ")
+    }
+
+    /// Value of the component field `field_name` after applying the upgrades of `skill`
+    /// up to `level` to a zeroed component, ignoring any character specific base values.
+    pub fn field_at_level(&self, skill: Skill, level: Level, field_name: &str) -> f32 {
+        let Some(levels) = self.upgrades.get(&skill) else {
+            return 0.;
+        };
+        let mut field = 0.;
+        for spec in levels.iter().take(level.0 as usize + 1) {
+            for (attr, val) in spec {
+                if self
+                    .attributes
+                    .get(attr)
+                    .is_some_and(|attr_meta| attr_meta.field_name == field_name)
+                {
+                    field = val.apply_to_f32(field);
+                }
+            }
+        }
+        field
+    }
+}
+
+pub const SKILLS_ASSET_PATH: &str = "all.skills.ron";
+
 fn setup_skills_asset_handle(mut skills_meta: ResMut<Skills>, asset_server: Res<AssetServer>) {
-    skills_meta.handle = asset_server.load("all.skills.ron");
+    skills_meta.handle = asset_server.load(SKILLS_ASSET_PATH);
 }
 
 #[derive(Component)]
@@ -430,6 +1493,7 @@ pub struct HotReloadEquippedSkills;
 
 fn skills_asset_on_load(
     mut skills: ResMut<Skills>,
+    registry: Res<SkillRegistry>,
     mut skills_asset_events: EventReader<AssetEvent<SkillsAsset>>,
     skills_assets: Res<Assets<SkillsAsset>>,
     q_equipped: Query<(Entity, &EquippedSkills), With<HotReloadEquippedSkills>>,
@@ -437,50 +1501,217 @@ fn skills_asset_on_load(
 ) {
     for ev in skills_asset_events.read() {
         let h = skills.handle.clone();
-        if ev.is_loaded_with_dependencies(&h) {
-            if let Some(asset) = skills_assets.get(&h) {
-                // hot reload skills meta
-                skills.skills = asset.skills.clone();
-                skills.attributes = asset.attributes.clone();
-                skills.attributes_inv.clear();
-                for (attr, attr_mata) in &asset.attributes {
-                    skills
-                        .attributes_inv
-                        .insert(attr_mata.field_name.clone(), *attr);
+        if ev.is_loaded_with_dependencies(&h)
+            && let Some(asset) = skills_assets.get(&h)
+        {
+            // hot reload skills meta
+            skills.skills = asset.skills.clone();
+            skills.components.clear();
+            for (skill, skill_meta) in &asset.skills {
+                if let Some(component) = registry.get(&skill_meta.component) {
+                    skills.components.insert(*skill, component.type_id);
+                } else {
+                    error!(
+                        "Component {} not registered for skill {skill:?}!",
+                        skill_meta.component
+                    );
                 }
-                skills.upgrades = asset.upgrades.clone();
-
-                // hot reload skill components
-                for (entity, equipped) in &q_equipped {
-                    let mut specs = SkillSpecs(HashMap::new());
-                    for (skill, level) in &equipped.equipped {
-                        if let Some(levels) = skills.upgrades.get(skill) {
-                            if let Some(spec) = level.index(levels) {
-                                let mut new_spec = HashMap::new();
-                                for (attr, val) in spec {
-                                    if matches!(val, Value::F(_) | Value::U(_)) {
-                                        new_spec.insert(*attr, *val);
-                                    }
-                                }
-                                if !new_spec.is_empty() {
-                                    specs.0.insert(*skill, (*level, new_spec));
+            }
+            skills.attributes = asset.attributes.clone();
+            skills.attributes_inv.clear();
+            for (attr, attr_mata) in &asset.attributes {
+                skills
+                    .attributes_inv
+                    .insert(attr_mata.field_name.clone(), *attr);
+            }
+            skills.upgrades = asset.upgrades.clone();
+            skills.statuses = asset.statuses.clone();
+            skills.offers = asset.offers.clone();
+            skills.evolutions = asset.evolutions.clone();
+            skills.passives = asset.passives.clone();
+            skills.xp_curve = asset.xp_curve.clone();
+            skills.bonuses = asset.bonuses.clone();
+
+            // hot reload skill components
+            for (entity, equipped) in &q_equipped {
+                let mut specs = SkillSpecs(HashMap::new());
+                for (skill, level) in &equipped.equipped {
+                    if let Some(levels) = skills.upgrades.get(skill) {
+                        if let Some(spec) = level.index(levels) {
+                            let mut new_spec = HashMap::new();
+                            for (attr, val) in spec {
+                                if matches!(val, Value::F(_) | Value::U(_) | Value::Ref(..)) {
+                                    new_spec.insert(*attr, *val);
                                 }
-                            } else {
-                                error!(
-                                    "Hot reload: did not find level {level} upgrades for equipped skill {skill:?}."
-                                );
+                            }
+                            if !new_spec.is_empty() {
+                                specs.0.insert(*skill, (*level, new_spec));
                             }
                         } else {
                             error!(
-                                "Hot reload: did not find upgrades for equipped skill {skill:?}."
+                                "Hot reload: did not find level {level} upgrades for equipped skill {skill:?}."
                             );
                         }
-                    }
-                    if !specs.0.is_empty() {
-                        cmd.entity(entity).insert(specs);
+                    } else {
+                        error!("Hot reload: did not find upgrades for equipped skill {skill:?}.");
                     }
                 }
+                if !specs.0.is_empty() {
+                    cmd.entity(entity).insert(specs);
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> SkillRegistry {
+        let mut registry = SkillRegistry::default();
+        register_skill_components(&mut registry);
+        registry
+    }
+
+    fn skills_asset() -> SkillsAsset {
+        ron::de::from_bytes(include_bytes!("../../assets/all.skills.ron")).unwrap()
+    }
+
+    fn spec(attribute: &str, value: Value) -> SkillSpec {
+        SkillSpec::from_iter([(Attribute::new(attribute), value)])
+    }
+
+    #[test]
+    fn shipped_asset_is_valid() {
+        skills_asset().validate(&registry()).unwrap();
+    }
+
+    #[test]
+    fn spec_errors() {
+        let (asset, registry) = (skills_asset(), registry());
+        let health = Skill::new("Health");
+        assert!(matches!(
+            asset.validate_spec(
+                &registry,
+                Skill::new("Fireball"),
+                &spec("MaxHp", Value::U(1))
+            ),
+            Err(SkillSpecError::UndeclaredSkill(_))
+        ));
+        assert!(matches!(
+            asset.validate_spec(&registry, health, &spec("Mana", Value::U(1))),
+            Err(SkillSpecError::UndeclaredAttribute(_))
+        ));
+        assert!(matches!(
+            asset.validate_spec(&registry, health, &spec("Range", Value::F(1.))),
+            Err(SkillSpecError::FieldNotFound { .. })
+        ));
+        assert!(matches!(
+            asset.validate_spec(&registry, health, &spec("MaxHp", Value::F(1.5))),
+            Err(SkillSpecError::ValueTypeMismatch { .. })
+        ));
+        asset
+            .validate_spec(&registry, health, &spec("MaxHp", Value::AddU(10)))
+            .unwrap();
+    }
+
+    #[test]
+    fn ref_errors() {
+        let (asset, registry) = (skills_asset(), registry());
+        let laser = Skill::new("Laser");
+        let dps = |value| spec("Dps", value);
+        asset
+            .validate_spec(
+                &registry,
+                laser,
+                &dps(Value::AddRef(
+                    Skill::new("Health"),
+                    Attribute::new("MaxHp"),
+                    0.1,
+                )),
+            )
+            .unwrap();
+        assert!(matches!(
+            asset.validate_spec(
+                &registry,
+                laser,
+                &dps(Value::Ref(
+                    Skill::new("Fireball"),
+                    Attribute::new("MaxHp"),
+                    0.1
+                )),
+            ),
+            Err(SkillSpecError::UndeclaredSkill(_))
+        ));
+        assert!(matches!(
+            asset.validate_spec(
+                &registry,
+                laser,
+                &dps(Value::Ref(
+                    Skill::new("Health"),
+                    Attribute::new("Mana"),
+                    0.1
+                )),
+            ),
+            Err(SkillSpecError::UndeclaredAttribute(_))
+        ));
+        assert!(matches!(
+            asset.validate_spec(
+                &registry,
+                laser,
+                &dps(Value::AddRef(
+                    Skill::new("Health"),
+                    Attribute::new("Range"),
+                    0.1
+                )),
+            ),
+            Err(SkillSpecError::FieldNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn passive_spec_errors() {
+        let (asset, registry) = (skills_asset(), registry());
+        asset
+            .validate_passive_spec(&registry, &spec("Range", Value::Perc(10.)))
+            .unwrap();
+        assert!(matches!(
+            asset.validate_passive_spec(&registry, &spec("MaxHp", Value::Perc(10.))),
+            Err(SkillSpecError::NoFittingField { .. })
+        ));
+    }
+
+    #[test]
+    fn asset_errors() {
+        let registry = registry();
+        let mut asset = skills_asset();
+        asset.xp_curve = XpCurve::Constant(0);
+        assert!(matches!(
+            asset.validate(&registry),
+            Err(SkillsAssetLoaderError::InvalidXpCurve(_))
+        ));
+
+        let mut asset = skills_asset();
+        asset.offers.count = 0;
+        assert!(matches!(
+            asset.validate(&registry),
+            Err(SkillsAssetLoaderError::InvalidOfferCount(0))
+        ));
+
+        let mut asset = skills_asset();
+        asset.upgrades.insert(Skill::new("Melee"), vec![]);
+        assert!(matches!(
+            asset.validate(&registry),
+            Err(SkillsAssetLoaderError::EmptyUpgrades(_))
+        ));
+
+        let mut asset = skills_asset();
+        let laser = asset.skills.get_mut(&Skill::new("Laser")).unwrap();
+        laser.inflicts = Some(Status::new("Doom"));
+        assert!(matches!(
+            asset.validate(&registry),
+            Err(SkillsAssetLoaderError::UndeclaredStatus { .. })
+        ));
+    }
+}
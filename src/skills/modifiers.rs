@@ -0,0 +1,199 @@
+use std::fmt;
+
+use bevy::{ecs::component::Mutable, platform::collections::HashMap, prelude::*};
+
+use crate::app::AppState;
+
+use super::{Attribute, Skill, Skills, Value, apply_value, xp::XpGatherState};
+
+pub struct ModifiersPlugin;
+
+impl Plugin for ModifiersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, tick_modifiers.run_if(in_state(AppState::Run)));
+    }
+}
+
+/// Values of skill component `T` set by the skill specs, before any [`Modifier`].
+#[derive(Component)]
+pub struct SkillBase<T>(pub T);
+
+/// Change of an attribute on top of the values set by the skill specs, e.g. a timed buff.
+#[derive(Clone, Debug)]
+pub struct Modifier {
+    /// Name of what added the modifier, like a status.
+    pub source: &'static str,
+    pub attribute: Attribute,
+    pub value: Value,
+    /// Seconds until the modifier is removed, or `None` to keep it until its source
+    /// removes it.
+    pub remaining: Option<f32>,
+}
+
+impl Modifier {
+    /// Absolute values apply first, then additive values, then multipliers, then clamps.
+    fn order(&self) -> u8 {
+        match self.value {
+            Value::F(_) | Value::U(_) | Value::Ref(..) => 0,
+            Value::AddF(_) | Value::AddU(_) | Value::AddRef(..) | Value::PerLevel(_) => 1,
+            Value::Perc(_) | Value::Mul(_) => 2,
+            Value::Min(_) | Value::Max(_) => 3,
+        }
+    }
+}
+
+/// Modifiers of an entity, applied to each of its skill components with a field matching
+/// their attribute. Changing the stack recomputes the components from their [`SkillBase`].
+#[derive(Component, Default)]
+pub struct SkillModifiers(Vec<Modifier>);
+
+impl SkillModifiers {
+    /// Adds `modifier`, replacing the one with the same source and attribute.
+    pub fn add(&mut self, modifier: Modifier) {
+        if let Some(old) = self
+            .0
+            .iter_mut()
+            .find(|m| m.source == modifier.source && m.attribute == modifier.attribute)
+        {
+            *old = modifier;
+        } else {
+            self.0.push(modifier);
+            self.0.sort_by_key(Modifier::order);
+        }
+    }
+
+    pub fn remove_source(&mut self, source: &str) {
+        self.0.retain(|m| m.source != source);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Modifier> {
+        self.0.iter()
+    }
+
+    /// Breakdown of the value of field `field_name` of skill component `T`.
+    pub fn breakdown<T: Struct>(
+        &self,
+        skills: &Skills,
+        base: &SkillBase<T>,
+        component: &T,
+        field_name: &str,
+    ) -> Option<FieldBreakdown> {
+        Some(FieldBreakdown {
+            base: field_as_f32(base.0.field(field_name)?)?,
+            modifiers: self
+                .0
+                .iter()
+                .filter(|m| {
+                    skills
+                        .attributes
+                        .get(&m.attribute)
+                        .is_some_and(|attr_meta| attr_meta.field_name == field_name)
+                })
+                .map(|m| (m.source, m.value))
+                .collect(),
+            effective: field_as_f32(component.field(field_name)?)?,
+        })
+    }
+}
+
+fn field_as_f32(field: &dyn PartialReflect) -> Option<f32> {
+    field
+        .try_downcast_ref::<f32>()
+        .copied()
+        .or_else(|| field.try_downcast_ref::<u32>().map(|v| *v as f32))
+}
+
+/// Base and effective value of a skill component field, and the modifiers in between,
+/// e.g. for tooltips. Integer fields are converted to `f32`.
+pub struct FieldBreakdown {
+    pub base: f32,
+    pub modifiers: Vec<(&'static str, Value)>,
+    pub effective: f32,
+}
+
+impl fmt::Display for FieldBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (base {}",
+            Value::round(self.effective),
+            Value::round(self.base)
+        )?;
+        for (source, value) in &self.modifiers {
+            write!(f, ", {source} {value}")?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// Effective attribute values of an entity's skills, which [`Value::Ref`] and
+/// [`Value::AddRef`] refer to.
+#[derive(Component, Default)]
+pub struct AttributeValues(HashMap<(Skill, Attribute), f32>);
+
+impl AttributeValues {
+    pub fn get(&self, skill: Skill, attribute: Attribute) -> Option<f32> {
+        self.0.get(&(skill, attribute)).copied()
+    }
+}
+
+/// Sets the fields of `T` to their [`SkillBase`] values with the [`SkillModifiers`] applied,
+/// whenever either changes, and records them in the [`AttributeValues`].
+pub fn apply_skill_modifiers<T: Component<Mutability = Mutable> + Struct>(
+    skills: Res<Skills>,
+    mut q_skill: Query<(Entity, &mut T, Ref<SkillBase<T>>)>,
+    q_modifiers: Query<Ref<SkillModifiers>>,
+    mut q_values: Query<&mut AttributeValues>,
+    q_level: Query<&XpGatherState>,
+) {
+    for (entity, mut component, base) in &mut q_skill {
+        let modifiers = q_modifiers.get(entity).ok();
+        if !base.is_changed() && !modifiers.as_ref().is_some_and(|m| m.is_changed()) {
+            continue;
+        }
+        let Ok(mut values) = q_values.get_mut(entity) else {
+            continue;
+        };
+        let player_level = q_level
+            .get(entity)
+            .map_or(1, XpGatherState::get_player_level);
+        component.apply(base.0.as_partial_reflect());
+        for modifier in modifiers.iter().flat_map(|m| m.iter()) {
+            if let Some(fld) = skills
+                .attributes
+                .get(&modifier.attribute)
+                .and_then(|attr_meta| component.field_mut(&attr_meta.field_name))
+                && let Some(value) = modifier.value.resolve(&values, player_level)
+            {
+                apply_value(fld, value);
+            }
+        }
+        for skill in skills.bound_skills::<T>() {
+            for (attr, attr_meta) in &skills.attributes {
+                if let Some(value) = component
+                    .field(&attr_meta.field_name)
+                    .and_then(field_as_f32)
+                {
+                    values.0.insert((skill, *attr), value);
+                }
+            }
+        }
+    }
+}
+
+fn tick_modifiers(time: Res<Time>, mut q_modifiers: Query<&mut SkillModifiers>) {
+    for mut modifiers in &mut q_modifiers {
+        let mut expired = false;
+        for modifier in &mut modifiers.bypass_change_detection().0 {
+            if let Some(remaining) = &mut modifier.remaining {
+                *remaining -= time.delta_secs();
+                expired |= *remaining <= 0.;
+            }
+        }
+        if expired {
+            modifiers
+                .0
+                .retain(|m| m.remaining.is_none_or(|remaining| remaining > 0.));
+        }
+    }
+}
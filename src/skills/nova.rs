@@ -0,0 +1,120 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use crate::{
+    app::{AppState, Headless, RunRng},
+    physics::enemy_layer,
+    player::Player,
+};
+
+use super::{
+    CombatSystems, ground_circle_isometry,
+    health::{CriticalHits, DamageType},
+    status::{Disarmed, Hit, HitWriter},
+};
+
+pub struct NovaPlugin;
+
+impl Plugin for NovaPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<NovaState>().add_systems(
+            Update,
+            (
+                update_nova,
+                draw_nova.run_if(not(resource_exists::<Headless>)),
+            )
+                .chain()
+                .in_set(CombatSystems::Nova)
+                .run_if(in_state(AppState::Run)),
+        );
+    }
+}
+
+const PLAYER_NOVA_COLOR: Color = Color::srgb(1.0, 0.9, 0.2);
+const NPC_NOVA_COLOR: Color = Color::srgb(1.0, 0.4, 0.1);
+/// Seconds the expanding ring of a burst is drawn for.
+const NOVA_VFX_DURATION: f32 = 0.25;
+/// Alpha of the circle always drawn at the edge of the range.
+const NOVA_RANGE_ALPHA: f32 = 0.15;
+
+/// Damages all enemies in `range` of the owner once every `cooldown` seconds, pushing
+/// them `knockback` away.
+#[derive(Component, Reflect, Default)]
+#[require(NovaState)]
+pub struct Nova {
+    pub range: f32,
+    pub damage: f32,
+    pub cooldown: f32,
+    pub knockback: f32,
+    pub status_duration: f32,
+}
+
+#[derive(Component, Reflect, Default)]
+pub struct NovaState {
+    pub time_fired: f32,
+}
+
+fn update_nova(
+    time: Res<Time>,
+    q_space: SpatialQuery,
+    mut rng: ResMut<RunRng>,
+    mut q_nova: Query<(Entity, &Nova, &mut NovaState, &Transform, Has<Player>)>,
+    q_source: Query<(Option<&CriticalHits>, Has<Disarmed>)>,
+    q_targets: Query<&Position>,
+    mut hits: HitWriter,
+) {
+    let now = time.elapsed_secs();
+    for (source, nova, mut state, tr_src, is_player) in &mut q_nova {
+        let (crit_hits, is_disarmed) = q_source.get(source).unwrap_or_default();
+        if is_disarmed || nova.range <= 0. || now - state.time_fired < nova.cooldown {
+            continue;
+        }
+        state.time_fired = now;
+        let pos = tr_src.translation;
+        for target in q_space.shape_intersections(
+            &Collider::sphere(nova.range),
+            pos,
+            Quat::default(),
+            &SpatialQueryFilter::from_mask([enemy_layer(is_player)]),
+        ) {
+            let (crit, multiplier) = CriticalHits::roll(crit_hits, &mut rng.combat);
+            let dir = q_targets.get(target).map_or(Vec3::ZERO, |target_pos| {
+                (target_pos.0 - pos).with_y(0.).normalize_or_zero()
+            });
+            hits.write::<Nova>(
+                Hit {
+                    target,
+                    source,
+                    damage: nova.damage * multiplier,
+                    kind: DamageType::Physical,
+                    crit,
+                    knockback: dir * nova.knockback,
+                },
+                nova.status_duration,
+            );
+        }
+    }
+}
+
+fn draw_nova(
+    time: Res<Time>,
+    q_nova: Query<(&Nova, &NovaState, &Transform, Has<Player>)>,
+    mut gizmos: Gizmos,
+) {
+    for (nova, state, tr_src, is_player) in &q_nova {
+        if nova.range <= 0. {
+            continue;
+        }
+        let color = if is_player {
+            PLAYER_NOVA_COLOR
+        } else {
+            NPC_NOVA_COLOR
+        };
+        let isometry = ground_circle_isometry(tr_src.translation);
+        gizmos.circle(isometry, nova.range, color.with_alpha(NOVA_RANGE_ALPHA));
+        let t = (time.elapsed_secs() - state.time_fired) / NOVA_VFX_DURATION;
+        if (0. ..1.).contains(&t) {
+            gizmos.circle(isometry, nova.range * t, color.with_alpha(1. - t));
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use super::{
+    EquippedSkills, Rarity, SkillSpec, Skills,
+    modifiers::{Modifier, SkillModifiers},
+    registry::Passive,
+};
+
+pub struct PassivePlugin;
+
+impl Plugin for PassivePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Passive>().add_systems(
+            Update,
+            reload_passive_modifiers.run_if(resource_changed::<Skills>),
+        );
+    }
+}
+
+/// Flat boost to attributes shared across skills, applied to every skill component of
+/// its owner with a field matching the attribute.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PassiveMeta {
+    pub ui_name: String,
+    /// Total values of the item at each level, unlike skill upgrade levels which build
+    /// on the previous ones.
+    pub levels: Vec<SkillSpec>,
+    /// Rarity of each level, common when missing.
+    #[serde(default)]
+    pub rarities: Vec<Rarity>,
+}
+
+/// Replaces the modifiers of `passive` with the values of its level `spec`.
+pub fn set_passive_modifiers(modifiers: &mut SkillModifiers, passive: Passive, spec: &SkillSpec) {
+    modifiers.remove_source(passive.name());
+    for (attribute, value) in spec {
+        modifiers.add(Modifier {
+            source: passive.name(),
+            attribute: *attribute,
+            value: *value,
+            remaining: None,
+        });
+    }
+}
+
+fn reload_passive_modifiers(
+    skills: Res<Skills>,
+    mut q_passives: Query<(&EquippedSkills, &mut SkillModifiers)>,
+) {
+    for (equipped, mut modifiers) in &mut q_passives {
+        for (passive, level) in equipped.passives() {
+            if let Some(spec) = skills
+                .passives
+                .get(passive)
+                .and_then(|passive_meta| level.index(&passive_meta.levels))
+            {
+                set_passive_modifiers(&mut modifiers, *passive, spec);
+            } else {
+                error!("Hot reload: did not find level {level} of passive {passive}.");
+            }
+        }
+    }
+}
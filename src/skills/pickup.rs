@@ -0,0 +1,386 @@
+use avian3d::prelude::*;
+use bevy::{platform::collections::HashSet, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    app::{AppState, Headless, InGame, RunStats},
+    physics::Layer,
+};
+
+use super::{
+    Skills,
+    health::{Health, KillEvent, MaxHealth},
+    xp::{XpGather, XpGatherState, init_gather_state},
+};
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<PickupKind>()
+            .init_resource::<Pickups>()
+            .add_state_scoped_event::<XpVacuumEvent>(AppState::Run)
+            .add_state_scoped_event::<PickupGatheredEvent>(AppState::Run)
+            .add_systems(
+                Startup,
+                setup_pickups.run_if(not(resource_exists::<Headless>)),
+            )
+            .add_systems(
+                Update,
+                (
+                    vacuum_on_boss_kill,
+                    vacuum_xp_drops,
+                    gather_pickups,
+                    apply_pickups,
+                    slow_pickups,
+                    merge_xp_drops,
+                )
+                    .chain()
+                    .after(init_gather_state)
+                    .run_if(in_state(AppState::Run)),
+            );
+    }
+}
+
+/// Above this many live XP drops, nearby resting drops are merged into bigger ones.
+pub const MAX_XP_DROPS: usize = 200;
+/// Distance within which XP drops are merged.
+pub const XP_DROP_MERGE_RANGE: f32 = 4.;
+
+/// What a pickup does once gathered.
+#[derive(Reflect, Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum PickupKind {
+    /// Adds XP to the gatherer.
+    Xp(u32),
+    /// Restores health, up to the max.
+    Heal(f32),
+    /// Pulls every XP drop to the gatherer, see [`XpVacuumEvent`].
+    Magnet,
+    /// Grants a free level up.
+    Chest,
+}
+
+impl PickupKind {
+    fn is_big_xp(xp: u32) -> bool {
+        xp > 5
+    }
+
+    pub fn get_height(&self) -> f32 {
+        match self {
+            PickupKind::Xp(xp) if PickupKind::is_big_xp(*xp) => 0.4,
+            PickupKind::Xp(_) => 0.2,
+            PickupKind::Heal(_) | PickupKind::Magnet => 0.3,
+            PickupKind::Chest => 0.5,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        match self {
+            PickupKind::Xp(xp) => *xp > 0,
+            PickupKind::Heal(hp) => *hp > 0.,
+            PickupKind::Magnet | PickupKind::Chest => true,
+        }
+    }
+}
+
+/// Entry of an NPC drop table, dropped with probability `chance` when the NPC dies.
+#[derive(Reflect, Clone, Debug, Deserialize)]
+pub struct PickupDrop {
+    pub pickup: PickupKind,
+    pub chance: f32,
+}
+
+#[derive(Component)]
+pub struct Pickup(pub PickupKind);
+
+/// Meshes and materials shared by all pickups.
+#[derive(Resource, Default)]
+pub struct Pickups {
+    /// Whether pickups get a mesh, false when headless.
+    pub render: bool,
+    pub mesh_small: Handle<Mesh>,
+    pub mesh_medium: Handle<Mesh>,
+    pub mesh_big: Handle<Mesh>,
+    pub mesh_chest: Handle<Mesh>,
+    pub xp_drop_small: Handle<StandardMaterial>,
+    pub xp_drop_big: Handle<StandardMaterial>,
+    pub heal: Handle<StandardMaterial>,
+    pub magnet: Handle<StandardMaterial>,
+    pub chest: Handle<StandardMaterial>,
+}
+
+impl Pickups {
+    fn get_handles(&self, kind: PickupKind) -> (Handle<Mesh>, Handle<StandardMaterial>) {
+        match kind {
+            PickupKind::Xp(xp) if PickupKind::is_big_xp(xp) => {
+                (self.mesh_big.clone(), self.xp_drop_big.clone())
+            }
+            PickupKind::Xp(_) => (self.mesh_small.clone(), self.xp_drop_small.clone()),
+            PickupKind::Heal(_) => (self.mesh_medium.clone(), self.heal.clone()),
+            PickupKind::Magnet => (self.mesh_medium.clone(), self.magnet.clone()),
+            PickupKind::Chest => (self.mesh_chest.clone(), self.chest.clone()),
+        }
+    }
+
+    /// Spawns a pickup on the ground below `position`.
+    pub fn spawn(&self, kind: PickupKind, position: Vec3, cmd: &mut Commands) -> Entity {
+        let h = kind.get_height();
+        let id = cmd
+            .spawn((
+                Pickup(kind),
+                Transform::from_translation(Vec3::new(position.x, h + 0.02, position.z)),
+                RigidBody::Kinematic,
+                Collider::sphere(h),
+                CollisionLayers::new([Layer::Building], [Layer::Building, Layer::Player]),
+                StateScoped(InGame),
+            ))
+            .id();
+        cmd.entity(id)
+            .insert(Name::new(format!("Pickup {kind:?} ({id:?})")));
+        if self.render {
+            let (mesh, material) = self.get_handles(kind);
+            cmd.entity(id)
+                .insert((Mesh3d(mesh), MeshMaterial3d(material)));
+        }
+        id
+    }
+}
+
+fn setup_pickups(
+    mut pickups: ResMut<Pickups>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut material = |base_color: Color| {
+        materials.add(StandardMaterial {
+            base_color,
+            metallic: 0.8,
+            perceptual_roughness: 0.4,
+            reflectance: 0.9,
+            ..default()
+        })
+    };
+    pickups.render = true;
+    pickups.xp_drop_small = material(Color::srgb(1.0, 4.0, 1.0));
+    pickups.xp_drop_big = material(Color::srgb(4.0, 1.0, 1.0));
+    pickups.heal = material(Color::srgb(4.0, 1.0, 3.0));
+    pickups.magnet = material(Color::srgb(1.0, 1.0, 4.0));
+    pickups.chest = material(Color::srgb(4.0, 3.0, 0.5));
+    let mut sphere =
+        |kind: PickupKind| meshes.add(Sphere::new(kind.get_height()).mesh().ico(4).unwrap());
+    pickups.mesh_small = sphere(PickupKind::Xp(1));
+    pickups.mesh_medium = sphere(PickupKind::Magnet);
+    pickups.mesh_big = sphere(PickupKind::Xp(u32::MAX));
+    pickups.mesh_chest = meshes.add(Cuboid::from_length(2. * PickupKind::Chest.get_height()));
+}
+
+/// Pulls every live XP drop to the nearest XP gatherer, regardless of its range.
+#[derive(Event)]
+pub struct XpVacuumEvent;
+
+/// XP drop pulled by a vacuum towards the gatherer.
+#[derive(Component)]
+pub struct XpVacuum(pub Entity);
+
+/// Sent when `gatherer` reaches a pickup, which is then despawned.
+#[derive(Event)]
+pub struct PickupGatheredEvent {
+    pub gatherer: Entity,
+    pub kind: PickupKind,
+}
+
+fn vacuum_on_boss_kill(
+    mut ev_kill: EventReader<KillEvent>,
+    mut ev_vacuum: EventWriter<XpVacuumEvent>,
+) {
+    if ev_kill.read().any(|ev| ev.is_boss) {
+        ev_vacuum.write(XpVacuumEvent);
+    }
+}
+
+fn vacuum_xp_drops(
+    mut ev_vacuum: EventReader<XpVacuumEvent>,
+    q_xp_gather: Query<(Entity, &Transform), With<XpGather>>,
+    q_pickup: Query<(Entity, &Transform, &Pickup)>,
+    mut cmd: Commands,
+) {
+    if ev_vacuum.read().count() == 0 {
+        return;
+    }
+    for (ent, tr_pickup, pickup) in &q_pickup {
+        if !matches!(pickup.0, PickupKind::Xp(_)) {
+            continue;
+        }
+        if let Some((gatherer, _)) = q_xp_gather.iter().min_by(|(_, tr1), (_, tr2)| {
+            let d1 = tr1.translation.distance_squared(tr_pickup.translation);
+            let d2 = tr2.translation.distance_squared(tr_pickup.translation);
+            d1.total_cmp(&d2)
+        }) {
+            cmd.entity(ent).insert(XpVacuum(gatherer));
+        }
+    }
+}
+
+/// Pulls pickups in range of an [`XpGather`] towards it, gathering those that reach it.
+fn gather_pickups(
+    time: Res<Time>,
+    q_space: SpatialQuery,
+    q_xp_gather: Query<(Entity, &Transform, &XpGather)>,
+    mut q_pickup: Query<(
+        Entity,
+        &Transform,
+        &mut LinearVelocity,
+        &Pickup,
+        Option<&XpVacuum>,
+    )>,
+    mut ev_gathered: EventWriter<PickupGatheredEvent>,
+    mut cmd: Commands,
+) {
+    let dt = time.delta_secs();
+    for (gatherer, tr_gatherer, xp_gather) in &q_xp_gather {
+        let mut pull = |(ent, tr_pickup, mut lin_vel, pickup, _): (
+            Entity,
+            &Transform,
+            Mut<LinearVelocity>,
+            &Pickup,
+            Option<&XpVacuum>,
+        )| {
+            let mut delta = tr_gatherer.translation - tr_pickup.translation;
+            if delta.length() < pickup.0.get_height() + 1. {
+                ev_gathered.write(PickupGatheredEvent {
+                    gatherer,
+                    kind: pickup.0,
+                });
+                cmd.entity(ent).despawn();
+            } else {
+                lin_vel.y = 0.;
+                let old_speed = lin_vel.length();
+                delta.y = 0.;
+                delta = delta.normalize() * (old_speed + dt * xp_gather.acceleration);
+                lin_vel.x = delta.x;
+                lin_vel.z = delta.z;
+            }
+        };
+        let is_vacuumed = |vacuum: Option<&XpVacuum>| vacuum.is_some_and(|v| v.0 == gatherer);
+
+        for ent in q_space
+            .shape_intersections(
+                &Collider::sphere(xp_gather.range),
+                tr_gatherer.translation,
+                Quat::default(),
+                &SpatialQueryFilter::from_mask([Layer::Building]),
+            )
+            .iter()
+        {
+            if let Ok(pickup) = q_pickup.get_mut(*ent)
+                && !is_vacuumed(pickup.4)
+            {
+                pull(pickup);
+            }
+        }
+        for pickup in &mut q_pickup {
+            if is_vacuumed(pickup.4) {
+                pull(pickup);
+            }
+        }
+    }
+}
+
+fn apply_pickups(
+    skills: Res<Skills>,
+    mut ev_gathered: EventReader<PickupGatheredEvent>,
+    mut ev_vacuum: EventWriter<XpVacuumEvent>,
+    mut run_stats: ResMut<RunStats>,
+    mut q_xp_gather: Query<(&mut XpGatherState, Option<(&mut Health, &MaxHealth)>)>,
+) {
+    for ev in ev_gathered.read() {
+        let Ok((mut xp_gather_state, health)) = q_xp_gather.get_mut(ev.gatherer) else {
+            continue;
+        };
+        match ev.kind {
+            PickupKind::Xp(xp) => {
+                xp_gather_state.gather(xp, &skills.xp_curve);
+                run_stats.xp += xp;
+            }
+            PickupKind::Heal(hp) => {
+                if let Some((mut health, max_health)) = health {
+                    health.0 = (health.0 + hp).min(max_health.max_hp as f32);
+                }
+            }
+            PickupKind::Magnet => {
+                ev_vacuum.write(XpVacuumEvent);
+            }
+            PickupKind::Chest => xp_gather_state.grant_level(),
+        }
+    }
+}
+
+fn slow_pickups(time: Res<Time>, mut q_pickup: Query<&mut LinearVelocity, With<Pickup>>) {
+    for mut lin_vel in &mut q_pickup {
+        let speed = lin_vel.length();
+        if speed > f32::EPSILON {
+            let dir = lin_vel.normalize_or_zero();
+            lin_vel.0 = (speed - time.delta_secs() * 5.).max(0.) * dir;
+        }
+    }
+}
+
+/// Keeps the number of XP drops in check after big waves by merging resting drops that
+/// lie close together, summing their XP.
+fn merge_xp_drops(
+    pickups: Res<Pickups>,
+    q_space: SpatialQuery,
+    q_pickup: Query<(Entity, &Transform, &LinearVelocity, &Pickup, Has<XpVacuum>)>,
+    mut cmd: Commands,
+) {
+    let resting_xp = |lin_vel: &LinearVelocity, pickup: &Pickup, vacuumed: bool| match pickup.0 {
+        PickupKind::Xp(xp) if !vacuumed && lin_vel.length_squared() < f32::EPSILON => Some(xp),
+        _ => None,
+    };
+    let mut live_drops = q_pickup
+        .iter()
+        .filter(|(_, _, _, pickup, _)| matches!(pickup.0, PickupKind::Xp(_)))
+        .count();
+    if live_drops <= MAX_XP_DROPS {
+        return;
+    }
+    let mut merged = HashSet::new();
+    for (ent, tr_pickup, lin_vel, pickup, vacuumed) in &q_pickup {
+        if live_drops <= MAX_XP_DROPS {
+            break;
+        }
+        if merged.contains(&ent) {
+            continue;
+        }
+        let Some(mut xp) = resting_xp(lin_vel, pickup, vacuumed) else {
+            continue;
+        };
+        let mut cluster = 0;
+        for other in q_space
+            .shape_intersections(
+                &Collider::sphere(XP_DROP_MERGE_RANGE),
+                tr_pickup.translation,
+                Quat::default(),
+                &SpatialQueryFilter::from_mask([Layer::Building]),
+            )
+            .iter()
+        {
+            if *other != ent
+                && !merged.contains(other)
+                && let Ok((_, _, lin_vel, other_pickup, vacuumed)) = q_pickup.get(*other)
+                && let Some(other_xp) = resting_xp(lin_vel, other_pickup, vacuumed)
+            {
+                xp = xp.saturating_add(other_xp);
+                merged.insert(*other);
+                cmd.entity(*other).despawn();
+                cluster += 1;
+            }
+        }
+        if cluster > 0 {
+            merged.insert(ent);
+            cmd.entity(ent).despawn();
+            pickups.spawn(PickupKind::Xp(xp), tr_pickup.translation, &mut cmd);
+            live_drops -= cluster;
+        }
+    }
+}
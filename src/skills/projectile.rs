@@ -0,0 +1,352 @@
+use avian3d::prelude::*;
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+};
+
+use crate::{
+    app::{AppState, Headless, InGame, RunRng},
+    npc::Npc,
+    physics::Layer,
+    player::Player,
+};
+
+use super::{
+    CombatSystems,
+    health::{CriticalHits, DamageType},
+    status::{Disarmed, Hit, HitWriter},
+};
+
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ProjectileState>()
+            .init_resource::<ProjectileHandles>()
+            .init_resource::<ProjectilePool>()
+            .add_systems(
+                Startup,
+                setup_assets.run_if(not(resource_exists::<Headless>)),
+            )
+            .add_systems(
+                OnTransition {
+                    exited: AppState::Menu,
+                    entered: AppState::Run,
+                },
+                reset_projectile_pool,
+            )
+            .add_systems(
+                Update,
+                (
+                    (projectile_target_npc, projectile_target_player),
+                    fire_projectiles,
+                    projectile_hits,
+                    expire_projectiles,
+                )
+                    .chain()
+                    .in_set(CombatSystems::Projectile)
+                    .run_if(in_state(AppState::Run)),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ProjectileHandles {
+    pub mesh: Handle<Mesh>,
+    pub player_material: Handle<StandardMaterial>,
+    pub npc_material: Handle<StandardMaterial>,
+}
+
+const PLAYER_PROJECTILE_COLOR: Color = Color::srgb(0.0, 4.0, 5.0);
+const NPC_PROJECTILE_COLOR: Color = Color::srgb(5.0, 0.5, 2.0);
+const PROJECTILE_RADIUS: f32 = 0.15;
+
+fn setup_assets(
+    mut handles: ResMut<ProjectileHandles>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    handles.mesh = meshes.add(Sphere::new(PROJECTILE_RADIUS).mesh().ico(2).unwrap());
+    handles.player_material = materials.add(StandardMaterial {
+        base_color: PLAYER_PROJECTILE_COLOR,
+        emissive: PLAYER_PROJECTILE_COLOR.into(),
+        perceptual_roughness: 1.0,
+        metallic: 0.,
+        reflectance: 0.,
+        ..default()
+    });
+    handles.npc_material = materials.add(StandardMaterial {
+        base_color: NPC_PROJECTILE_COLOR,
+        emissive: NPC_PROJECTILE_COLOR.into(),
+        perceptual_roughness: 1.0,
+        metallic: 0.,
+        reflectance: 0.,
+        ..default()
+    });
+}
+
+/// Fires `count` projectiles every `cooldown` seconds at the closest enemy in `range`.
+#[derive(Component, Reflect, Default)]
+#[require(ProjectileState)]
+pub struct Projectile {
+    pub range: f32,
+    pub speed: f32,
+    pub count: u32,
+    /// Angle in degrees between the first and the last projectile of a volley.
+    pub spread: f32,
+    /// Number of enemies a projectile passes through before it is spent.
+    pub pierce: u32,
+    pub lifetime: f32,
+    pub damage: f32,
+    pub cooldown: f32,
+    pub knockback: f32,
+    pub status_duration: f32,
+}
+
+#[derive(Component, Reflect, Default)]
+pub struct ProjectileState {
+    /// Position aimed at by the next volley.
+    pub target: Option<Vec3>,
+    pub time_fired: f32,
+}
+
+/// A fired projectile. Spent projectiles stay around disabled in the [`ProjectilePool`].
+#[derive(Component)]
+pub struct ProjectileShot {
+    pub source: Entity,
+    pub damage: f32,
+    pub crit: bool,
+    pub knockback: Vec3,
+    pub pierce: u32,
+    pub status_duration: f32,
+    pub time_expires: f32,
+    /// Entities already damaged, which pierced projectiles don't hit again.
+    pub hits: Vec<Entity>,
+    pub active: bool,
+}
+
+/// Spent projectile entities, reused by later volleys instead of spawning new ones.
+#[derive(Resource, Default)]
+pub struct ProjectilePool {
+    free: Vec<Entity>,
+}
+
+fn reset_projectile_pool(mut pool: ResMut<ProjectilePool>) {
+    pool.free.clear();
+}
+
+fn closest(pos: Vec3, targets: impl Iterator<Item = Vec3>) -> Option<Vec3> {
+    targets.min_by(|p1, p2| {
+        p1.distance_squared(pos)
+            .total_cmp(&p2.distance_squared(pos))
+    })
+}
+
+fn projectile_target_npc(
+    time: Res<Time>,
+    q_space: SpatialQuery,
+    mut q_projectile: Query<
+        (&mut ProjectileState, &Projectile, &Transform, Has<Disarmed>),
+        With<Player>,
+    >,
+    q_npc: Query<&Transform, With<Npc>>,
+) {
+    for (mut state, config, tr_player, is_disarmed) in &mut q_projectile {
+        if is_disarmed || time.elapsed_secs() - state.time_fired < config.cooldown {
+            continue;
+        }
+        let pos = tr_player.translation;
+        let npcs = q_space
+            .shape_intersections(
+                &Collider::sphere(config.range),
+                pos,
+                Quat::default(),
+                &SpatialQueryFilter::from_mask([Layer::NPC]),
+            )
+            .into_iter()
+            .filter_map(|ent| q_npc.get(ent).ok().map(|tr| tr.translation));
+        state.target = closest(pos, npcs);
+    }
+}
+
+fn projectile_target_player(
+    time: Res<Time>,
+    mut q_projectile: Query<
+        (&mut ProjectileState, &Projectile, &Transform, Has<Disarmed>),
+        Without<Player>,
+    >,
+    q_player: Query<&Transform, With<Player>>,
+) {
+    for (mut state, config, tr_src, is_disarmed) in &mut q_projectile {
+        if is_disarmed || time.elapsed_secs() - state.time_fired < config.cooldown {
+            continue;
+        }
+        let pos = tr_src.translation;
+        state.target = closest(pos, q_player.iter().map(|tr| tr.translation))
+            .filter(|target| (*target - pos).length() < config.range);
+    }
+}
+
+fn fire_projectiles(
+    time: Res<Time>,
+    handles: Res<ProjectileHandles>,
+    mut pool: ResMut<ProjectilePool>,
+    mut rng: ResMut<RunRng>,
+    mut q_projectile: Query<(
+        Entity,
+        &Projectile,
+        &mut ProjectileState,
+        &Transform,
+        Has<Player>,
+    )>,
+    q_crit: Query<&CriticalHits>,
+    mut cmd: Commands,
+) {
+    let now = time.elapsed_secs();
+    for (source, config, mut state, tr_src, is_player) in &mut q_projectile {
+        if config.count == 0 {
+            continue;
+        }
+        let Some(target) = state.target.take() else {
+            continue;
+        };
+        let src_pos = tr_src.translation;
+        state.time_fired = now;
+
+        // projectiles fly level, at the height of the target's center
+        let start = Vec3::new(src_pos.x, target.y, src_pos.z);
+        let aim = Vec2::new(target.x - start.x, target.z - start.z).normalize_or(Vec2::X);
+        let (layers, material) = if is_player {
+            (
+                CollisionLayers::new([Layer::Projectile], [Layer::NPC]),
+                handles.player_material.clone(),
+            )
+        } else {
+            (
+                CollisionLayers::new([Layer::Projectile], [Layer::Player]),
+                handles.npc_material.clone(),
+            )
+        };
+        let spread = config.spread.to_radians();
+        for i in 0..config.count {
+            let angle = if config.count > 1 {
+                spread * (i as f32 / (config.count - 1) as f32 - 0.5)
+            } else {
+                0.
+            };
+            let dir = aim.rotate(Vec2::from_angle(angle));
+            let dir = Vec3::new(dir.x, 0., dir.y);
+            let (crit, multiplier) = CriticalHits::roll(q_crit.get(source).ok(), &mut rng.combat);
+            let velocity = dir * config.speed;
+            let shot = ProjectileShot {
+                source,
+                damage: config.damage * multiplier,
+                crit,
+                knockback: dir * config.knockback,
+                pierce: config.pierce,
+                status_duration: config.status_duration,
+                time_expires: now + config.lifetime,
+                hits: vec![],
+                active: true,
+            };
+
+            if let Some(id) = pool.free.pop() {
+                cmd.entity(id)
+                    .insert((
+                        shot,
+                        Position(start),
+                        LinearVelocity(velocity),
+                        layers,
+                        CollidingEntities::default(),
+                        MeshMaterial3d(material.clone()),
+                        Visibility::Visible,
+                    ))
+                    .remove::<(ColliderDisabled, RigidBodyDisabled)>();
+                continue;
+            }
+
+            let id = cmd
+                .spawn((
+                    shot,
+                    Transform::from_translation(start),
+                    Position(start),
+                    RigidBody::Kinematic,
+                    LinearVelocity(velocity),
+                    Collider::sphere(PROJECTILE_RADIUS),
+                    Sensor,
+                    layers,
+                    CollidingEntities::default(),
+                    Mesh3d(handles.mesh.clone()),
+                    MeshMaterial3d(material.clone()),
+                    Visibility::Visible,
+                    NotShadowCaster,
+                    NotShadowReceiver,
+                    StateScoped(InGame),
+                ))
+                .id();
+            cmd.entity(id)
+                .insert(Name::new(format!("Projectile ({id:?})")));
+        }
+    }
+}
+
+fn projectile_hits(
+    mut q_shots: Query<(&mut ProjectileShot, &CollidingEntities)>,
+    mut hits: HitWriter,
+) {
+    for (mut shot, colliding) in &mut q_shots {
+        if !shot.active {
+            continue;
+        }
+        for target in colliding.iter() {
+            if shot.hits.contains(target) {
+                continue;
+            }
+            hits.write::<Projectile>(
+                Hit {
+                    target: *target,
+                    source: shot.source,
+                    damage: shot.damage,
+                    kind: DamageType::Physical,
+                    crit: shot.crit,
+                    knockback: shot.knockback,
+                },
+                shot.status_duration,
+            );
+            shot.hits.push(*target);
+            if shot.hits.len() > shot.pierce as usize {
+                shot.active = false;
+                break;
+            }
+        }
+    }
+}
+
+/// Returns spent and expired projectiles to the [`ProjectilePool`].
+fn expire_projectiles(
+    time: Res<Time>,
+    mut pool: ResMut<ProjectilePool>,
+    mut q_shots: Query<(
+        Entity,
+        &mut ProjectileShot,
+        &mut LinearVelocity,
+        &mut Visibility,
+    )>,
+    mut cmd: Commands,
+) {
+    let now = time.elapsed_secs();
+    for (entity, mut shot, mut linear_velocity, mut visibility) in &mut q_shots {
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+        if shot.active && now < shot.time_expires {
+            continue;
+        }
+        shot.active = false;
+        linear_velocity.0 = Vec3::ZERO;
+        *visibility = Visibility::Hidden;
+        cmd.entity(entity)
+            .insert((ColliderDisabled, RigidBodyDisabled));
+        pool.free.push(entity);
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    any::TypeId,
+    collections::HashSet as StdHashSet,
+    fmt,
+    sync::{Arc, LazyLock, Mutex, RwLock},
+};
+
+use bevy::{
+    ecs::component::Mutable,
+    platform::collections::HashMap,
+    prelude::*,
+    reflect::{GetTypeRegistration, StructInfo, TypeInfo, Typed},
+};
+use serde::{
+    Deserialize, Deserializer,
+    de::{self, Visitor},
+};
+
+use crate::app::AppState;
+
+use super::{ApplySkillSpecs, apply_skill_specs, modifiers::apply_skill_modifiers, remove_skills};
+
+// interned ids
+
+static INTERNED_IDS: LazyLock<Mutex<StdHashSet<&'static str>>> =
+    LazyLock::new(|| Mutex::new(StdHashSet::new()));
+
+/// Returns a `'static` copy of `name`, allocating it only the first time it is seen.
+fn intern(name: &str) -> &'static str {
+    let mut interned = INTERNED_IDS.lock().unwrap();
+    if let Some(id) = interned.get(name) {
+        return id;
+    }
+    let id: &'static str = Box::leak(name.to_string().into_boxed_str());
+    interned.insert(id);
+    id
+}
+
+struct IdVisitor;
+
+impl<'de> Visitor<'de> for IdVisitor {
+    type Value = &'static str;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an identifier")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(intern(v))
+    }
+}
+
+/// Declares an identifier type holding an interned name, deserialized from a RON
+/// identifier.
+macro_rules! interned_id {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Reflect, Debug, Hash)]
+        pub struct $name(&'static str);
+
+        impl $name {
+            pub fn new(name: &str) -> Self {
+                Self(intern(name))
+            }
+
+            pub fn name(&self) -> &'static str {
+                self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer.deserialize_identifier(IdVisitor).map($name)
+            }
+        }
+    };
+}
+
+interned_id! {
+    /// Skill identifier, declared by name in the `skills` section of `all.skills.ron`.
+    Skill
+}
+
+interned_id! {
+    /// Attribute identifier, declared by name in the `attributes` section of `all.skills.ron`.
+    Attribute
+}
+
+interned_id! {
+    /// Status identifier, declared by name in the `statuses` section of `all.skills.ron`.
+    Status
+}
+
+interned_id! {
+    /// Passive item identifier, declared by name in the `passives` section of `all.skills.ron`.
+    Passive
+}
+
+// skill component registration
+
+/// Reflected components that skills can be bound to, keyed by their short type path.
+///
+/// Cheap to clone, so asset loaders can keep a handle to it for validation.
+#[derive(Resource, Clone, Default)]
+pub struct SkillRegistry {
+    components: Arc<RwLock<HashMap<&'static str, SkillComponentInfo>>>,
+}
+
+#[derive(Copy, Clone)]
+pub struct SkillComponentInfo {
+    pub type_id: TypeId,
+    pub info: &'static StructInfo,
+}
+
+impl SkillRegistry {
+    pub fn register<T: Typed + TypePath>(&self) {
+        let TypeInfo::Struct(info) = T::type_info() else {
+            error!("Skill component {} is not a struct!", T::short_type_path());
+            return;
+        };
+        self.components.write().unwrap().insert(
+            T::short_type_path(),
+            SkillComponentInfo {
+                type_id: TypeId::of::<T>(),
+                info,
+            },
+        );
+    }
+
+    pub fn get(&self, component: &str) -> Option<SkillComponentInfo> {
+        self.components.read().unwrap().get(component).copied()
+    }
+}
+
+pub trait RegisterSkillComponent {
+    /// Makes `T` available as a skill component, so that any skill whose `component`
+    /// in the skills asset is `T`'s type name gets its specs applied to `T`'s fields.
+    fn register_skill_component<T>(&mut self) -> &mut Self
+    where
+        T: Component<Mutability = Mutable>
+            + Struct
+            + Typed
+            + TypePath
+            + Default
+            + GetTypeRegistration;
+}
+
+impl RegisterSkillComponent for App {
+    fn register_skill_component<T>(&mut self) -> &mut Self
+    where
+        T: Component<Mutability = Mutable>
+            + Struct
+            + Typed
+            + TypePath
+            + Default
+            + GetTypeRegistration,
+    {
+        self.register_type::<T>();
+        self.world_mut()
+            .get_resource_or_init::<SkillRegistry>()
+            .register::<T>();
+        self.add_systems(
+            Update,
+            (
+                (remove_skills::<T>, apply_skill_specs::<T>)
+                    .chain()
+                    .in_set(ApplySkillSpecs),
+                apply_skill_modifiers::<T>,
+            )
+                .chain()
+                .run_if(in_state(AppState::Run)),
+        )
+    }
+}
+
+impl RegisterSkillComponent for SkillRegistry {
+    fn register_skill_component<T>(&mut self) -> &mut Self
+    where
+        T: Component<Mutability = Mutable>
+            + Struct
+            + Typed
+            + TypePath
+            + Default
+            + GetTypeRegistration,
+    {
+        self.register::<T>();
+        self
+    }
+}
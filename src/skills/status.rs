@@ -0,0 +1,323 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::Deserialize;
+
+use crate::app::AppState;
+
+use super::{
+    Attribute, CombatSystems, EquippedSkills, Skills, Value,
+    health::{DamageType, TakeDamageEvent},
+    modifiers::{Modifier, SkillModifiers},
+    registry::{Skill, Status},
+};
+
+pub struct StatusPlugin;
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Status>()
+            .add_state_scoped_event::<ApplyStatusEvent>(AppState::Run)
+            .add_systems(
+                Update,
+                (apply_status, tick_status)
+                    .chain()
+                    .in_set(CombatSystems::Status)
+                    .run_if(in_state(AppState::Run)),
+            );
+    }
+}
+
+/// How a status applied to a target that already has it combines with the active one.
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub enum Stacking {
+    /// Restarts the duration, when longer than what is left.
+    Refresh,
+    /// Adds the duration to what is left.
+    Extend,
+    /// Adds a stack, up to the given max, and refreshes the duration.
+    Stack(u32),
+    /// Leaves the active status unchanged.
+    Ignore,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub enum StatusEffect {
+    /// Damage per second per stack.
+    Damage(f32, DamageType),
+    /// Multiplies the movement speed.
+    Speed(f32),
+    /// Keeps the owner from targeting with its lasers and projectiles, and from using
+    /// its novas and melee.
+    Disarm,
+    /// Modifies an attribute of the owner's skills while the status lasts.
+    Modify(Attribute, Value),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StatusMeta {
+    pub ui_name: String,
+    pub stacking: Stacking,
+    pub effects: Vec<StatusEffect>,
+}
+
+#[derive(Event)]
+pub struct ApplyStatusEvent {
+    pub target: Entity,
+    /// Entity whose skill inflicted the status, credited with its damage.
+    pub source: Entity,
+    pub skill: Option<Skill>,
+    pub status: Status,
+    pub duration: f32,
+}
+
+pub struct ActiveStatus {
+    pub status: Status,
+    pub source: Entity,
+    pub skill: Option<Skill>,
+    pub remaining: f32,
+    pub stacks: u32,
+    /// Effects of the status when it was first applied.
+    pub effects: Vec<StatusEffect>,
+}
+
+impl ActiveStatus {
+    /// Applies the status again for `duration` seconds, following `stacking`. Returns
+    /// whether the status changed.
+    fn reapply(&mut self, stacking: Stacking, duration: f32) -> bool {
+        match stacking {
+            Stacking::Refresh => self.remaining = self.remaining.max(duration),
+            Stacking::Extend => self.remaining += duration,
+            Stacking::Stack(max_stacks) => {
+                self.stacks = (self.stacks + 1).min(max_stacks);
+                self.remaining = self.remaining.max(duration);
+            }
+            Stacking::Ignore => return false,
+        }
+        true
+    }
+}
+
+/// Statuses active on an entity, required by [`Health`](super::health::Health).
+#[derive(Component, Default)]
+pub struct StatusEffects(pub Vec<ActiveStatus>);
+
+impl StatusEffects {
+    /// Product of the speed multipliers of all active statuses.
+    pub fn speed_multiplier(&self) -> f32 {
+        self.effects()
+            .filter_map(|effect| match effect {
+                StatusEffect::Speed(multiplier) => Some(*multiplier),
+                _ => None,
+            })
+            .product()
+    }
+
+    fn effects(&self) -> impl Iterator<Item = &StatusEffect> {
+        self.0.iter().flat_map(|active| &active.effects)
+    }
+}
+
+/// Marks entities with an active [`StatusEffect::Disarm`].
+#[derive(Component)]
+pub struct Disarmed;
+
+/// Hit of a skill, written by [`HitWriter`] as a [`TakeDamageEvent`].
+pub struct Hit {
+    pub target: Entity,
+    pub source: Entity,
+    /// Damage before the target's resistances.
+    pub damage: f32,
+    pub kind: DamageType,
+    pub crit: bool,
+    pub knockback: Vec3,
+}
+
+/// Writes skill hits, inflicting the status of the skill on the target.
+#[derive(SystemParam)]
+pub struct HitWriter<'w, 's> {
+    skills: Res<'w, Skills>,
+    q_equipped: Query<'w, 's, &'static EquippedSkills>,
+    ev_take_damage: EventWriter<'w, TakeDamageEvent>,
+    ev_apply_status: EventWriter<'w, ApplyStatusEvent>,
+}
+
+impl HitWriter<'_, '_> {
+    /// Writes `hit` of the skill bound to component `T`, crediting it to the skill of
+    /// the source equipped for `T`. When `status_duration` is positive and the skill
+    /// inflicts a status, the status is also applied for that many seconds.
+    pub fn write<T: 'static>(&mut self, hit: Hit, status_duration: f32) {
+        let skill = self.q_equipped.get(hit.source).ok().and_then(|equipped| {
+            self.skills
+                .bound_skills::<T>()
+                .find(|skill| equipped.is_equipped(*skill))
+        });
+        if status_duration > 0.
+            && let Some(status) = self.skills.inflicted_status::<T>()
+        {
+            self.ev_apply_status.write(ApplyStatusEvent {
+                target: hit.target,
+                source: hit.source,
+                skill,
+                status,
+                duration: status_duration,
+            });
+        }
+        self.ev_take_damage.write(TakeDamageEvent {
+            target: hit.target,
+            source: hit.source,
+            skill,
+            damage: hit.damage,
+            kind: hit.kind,
+            crit: hit.crit,
+            knockback: hit.knockback,
+        });
+    }
+}
+
+fn apply_status(
+    skills: Res<Skills>,
+    mut ev_apply_status: EventReader<ApplyStatusEvent>,
+    mut q_status: Query<(&mut StatusEffects, Option<&mut SkillModifiers>)>,
+) {
+    for ev in ev_apply_status.read() {
+        let Some(status_meta) = skills.statuses.get(&ev.status) else {
+            error!("Status {} not found!", ev.status);
+            continue;
+        };
+        let Ok((mut statuses, modifiers)) = q_status.get_mut(ev.target) else {
+            continue;
+        };
+        let remaining = if let Some(active) = statuses.0.iter_mut().find(|s| s.status == ev.status)
+        {
+            if !active.reapply(status_meta.stacking, ev.duration) {
+                continue;
+            }
+            active.source = ev.source;
+            active.skill = ev.skill;
+            active.remaining
+        } else {
+            statuses.0.push(ActiveStatus {
+                status: ev.status,
+                source: ev.source,
+                skill: ev.skill,
+                remaining: ev.duration,
+                stacks: 1,
+                effects: status_meta.effects.clone(),
+            });
+            ev.duration
+        };
+
+        // the modifiers expire together with the status
+        let Some(mut modifiers) = modifiers else {
+            continue;
+        };
+        for effect in &status_meta.effects {
+            if let StatusEffect::Modify(attribute, value) = effect {
+                modifiers.add(Modifier {
+                    source: ev.status.name(),
+                    attribute: *attribute,
+                    value: *value,
+                    remaining: Some(remaining),
+                });
+            }
+        }
+    }
+}
+
+fn tick_status(
+    time: Res<Time>,
+    mut q_status: Query<(Entity, &mut StatusEffects, Has<Disarmed>)>,
+    mut ev_take_damage: EventWriter<TakeDamageEvent>,
+    mut cmd: Commands,
+) {
+    for (entity, mut statuses, is_disarmed) in &mut q_status {
+        if statuses.0.is_empty() && !is_disarmed {
+            continue;
+        }
+        for active in &mut statuses.0 {
+            let dt = time.delta_secs().min(active.remaining);
+            for effect in &active.effects {
+                if let StatusEffect::Damage(dps, kind) = effect {
+                    ev_take_damage.write(TakeDamageEvent {
+                        target: entity,
+                        source: active.source,
+                        skill: active.skill,
+                        damage: dt * dps * active.stacks as f32,
+                        kind: *kind,
+                        crit: false,
+                        knockback: Vec3::ZERO,
+                    });
+                }
+            }
+            active.remaining -= time.delta_secs();
+        }
+        statuses.0.retain(|active| active.remaining > 0.);
+
+        let disarm = statuses
+            .effects()
+            .any(|effect| matches!(effect, StatusEffect::Disarm));
+        if disarm && !is_disarmed {
+            cmd.entity(entity).try_insert(Disarmed);
+        } else if !disarm && is_disarmed {
+            cmd.entity(entity).try_remove::<Disarmed>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn active(remaining: f32) -> ActiveStatus {
+        ActiveStatus {
+            status: Status::new("Burning"),
+            source: Entity::PLACEHOLDER,
+            skill: None,
+            remaining,
+            stacks: 1,
+            effects: vec![StatusEffect::Speed(0.5), StatusEffect::Speed(0.8)],
+        }
+    }
+
+    #[test]
+    fn refresh_keeps_the_longer_duration() {
+        let mut status = active(2.);
+        assert!(status.reapply(Stacking::Refresh, 1.));
+        assert_eq!(status.remaining, 2.);
+        assert!(status.reapply(Stacking::Refresh, 3.));
+        assert_eq!(status.remaining, 3.);
+        assert_eq!(status.stacks, 1);
+    }
+
+    #[test]
+    fn extend_adds_the_duration() {
+        let mut status = active(2.);
+        assert!(status.reapply(Stacking::Extend, 1.5));
+        assert_eq!(status.remaining, 3.5);
+        assert_eq!(status.stacks, 1);
+    }
+
+    #[test]
+    fn stack_up_to_the_max() {
+        let mut status = active(2.);
+        for _ in 0..3 {
+            assert!(status.reapply(Stacking::Stack(3), 1.));
+        }
+        assert_eq!(status.stacks, 3);
+        assert_eq!(status.remaining, 2.);
+    }
+
+    #[test]
+    fn ignore_leaves_the_status_unchanged() {
+        let mut status = active(2.);
+        assert!(!status.reapply(Stacking::Ignore, 5.));
+        assert_eq!(status.remaining, 2.);
+        assert_eq!(status.stacks, 1);
+    }
+
+    #[test]
+    fn speed_multipliers_multiply() {
+        let statuses = StatusEffects(vec![active(1.)]);
+        assert!((statuses.speed_multiplier() - 0.4).abs() < f32::EPSILON);
+        assert_eq!(StatusEffects::default().speed_multiplier(), 1.);
+    }
+}
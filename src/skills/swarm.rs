@@ -4,9 +4,12 @@ use avian3d::prelude::*;
 use bevy::prelude::*;
 use rand::prelude::*;
 
-use crate::{app::AppState, player::Player};
+use crate::{
+    app::{AppState, RunRng},
+    player::Player,
+};
 
-use super::{IsSkill, Skill, apply_skill_specs};
+use super::{modifiers::apply_skill_modifiers, status::StatusEffects};
 
 pub struct SwarmPlugin;
 
@@ -14,7 +17,10 @@ impl Plugin for SwarmPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            (apply_skill_specs::<Swarm>, move_swarm).run_if(in_state(AppState::Run)),
+            // once newly spawned NPCs have their speed and range
+            move_swarm
+                .after(apply_skill_modifiers::<Swarm>)
+                .run_if(in_state(AppState::Run)),
         );
     }
 }
@@ -25,35 +31,42 @@ pub struct Swarm {
     pub range: f32,
 }
 
-impl IsSkill for Swarm {
-    fn skill() -> Skill {
-        Skill::Swarm
-    }
-}
-
 const ROAM_SPEED: f32 = 0.5;
 
 fn move_swarm(
-    mut q_npc: Query<(&Swarm, &Position, &mut LinearVelocity)>,
+    mut q_npc: Query<(
+        &Swarm,
+        &Position,
+        &mut LinearVelocity,
+        Option<&StatusEffects>,
+    )>,
     q_player: Query<&Position, With<Player>>,
+    mut rng: ResMut<RunRng>,
 ) {
     let Ok(player_pos) = q_player.single() else {
-        for (_, _, mut lin_vel) in &mut q_npc {
+        for (_, _, mut lin_vel, _) in &mut q_npc {
             lin_vel.x = 0.;
             lin_vel.y = 0.;
             lin_vel.z = 0.;
         }
         return;
     };
-    let mut rng = thread_rng();
-    for (swarm, npc_pos, mut lin_vel) in &mut q_npc {
+    let rng = &mut rng.swarm;
+    for (swarm, npc_pos, mut lin_vel, statuses) in &mut q_npc {
         lin_vel.y = 0.;
+        let multiplier = statuses.map_or(1., StatusEffects::speed_multiplier);
+        if multiplier <= 0. {
+            lin_vel.x = 0.;
+            lin_vel.z = 0.;
+            continue;
+        }
         let delta = Vec2::new(player_pos.x - npc_pos.x, player_pos.z - npc_pos.z);
         let dir = {
             if delta.length() < swarm.range {
                 delta.normalize() * swarm.speed
             } else {
-                let vel = Vec2::new(lin_vel.x, lin_vel.z);
+                // roaming keeps turning the last velocity, taken without the slow down
+                let vel = Vec2::new(lin_vel.x, lin_vel.z) / multiplier;
                 let (max_angle, new_vel) = {
                     if vel.length() > ROAM_SPEED / 2. && vel.length() < 2. * ROAM_SPEED {
                         (PI / 45., vel)
@@ -66,7 +79,7 @@ fn move_swarm(
                 rot.rotate(new_vel)
             }
         };
-        lin_vel.x = dir.x;
-        lin_vel.z = dir.y;
+        lin_vel.x = dir.x * multiplier;
+        lin_vel.z = dir.y * multiplier;
     }
 }
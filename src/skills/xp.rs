@@ -1,89 +1,79 @@
-use avian3d::prelude::*;
 use bevy::prelude::*;
+use serde::Deserialize;
 
-use crate::{app::AppState, physics::Layer};
-
-use super::{IsSkill, Skill, apply_skill_specs};
+use crate::app::AppState;
 
 pub struct XpPlugin;
 
 impl Plugin for XpPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<XpDrops>()
-            .add_systems(Startup, setup_xp_drops)
-            .add_systems(
-                Update,
-                (
-                    apply_skill_specs::<XpGather>,
-                    init_gather_state,
-                    gather_xp,
-                    slow_xp_drops,
-                )
-                    .run_if(in_state(AppState::Run)),
-            );
+        app.add_systems(Update, init_gather_state.run_if(in_state(AppState::Run)));
     }
 }
 
-#[derive(Resource, Default)]
-pub struct XpDrops {
-    pub xp_drop_small: Handle<StandardMaterial>,
-    pub xp_drop_big: Handle<StandardMaterial>,
+/// XP needed for each level, set by the `xp_curve` of `all.skills.ron`.
+#[derive(Clone, Debug, Deserialize)]
+pub enum XpCurve {
+    /// The same XP for every level.
+    Constant(u32),
+    /// XP for the first level, growing by a fixed amount each level.
+    Linear { base: u32, increment: u32 },
+    /// XP for each level, the last one repeating.
+    Table(Vec<u32>),
 }
 
-fn setup_xp_drops(mut xp_drops: ResMut<XpDrops>, mut materials: ResMut<Assets<StandardMaterial>>) {
-    xp_drops.xp_drop_small = materials.add(StandardMaterial {
-        base_color: Color::srgb(1.0, 4.0, 1.0),
-        metallic: 0.8,
-        perceptual_roughness: 0.4,
-        reflectance: 0.9,
-        ..default()
-    });
-    xp_drops.xp_drop_big = materials.add(StandardMaterial {
-        base_color: Color::srgb(4.0, 1.0, 1.0),
-        metallic: 0.8,
-        perceptual_roughness: 0.4,
-        reflectance: 0.9,
-        ..default()
-    });
+impl Default for XpCurve {
+    fn default() -> Self {
+        XpCurve::Constant(10)
+    }
 }
 
-#[derive(Component)]
-pub struct XpDrop(pub u32);
-
-impl XpDrop {
-    pub fn is_big(drop: u32) -> bool {
-        drop > 5
+impl XpCurve {
+    /// XP needed to go from gather level `level` to the next one, counting from 0.
+    pub fn xp_for_level(&self, level: u32) -> u32 {
+        match self {
+            XpCurve::Constant(xp) => *xp,
+            XpCurve::Linear { base, increment } => {
+                base.saturating_add(increment.saturating_mul(level))
+            }
+            XpCurve::Table(table) => table
+                .get(level as usize)
+                .or(table.last())
+                .copied()
+                .unwrap_or(u32::MAX),
+        }
     }
 
-    pub fn get_height(drop: u32) -> f32 {
-        if XpDrop::is_big(drop) { 0.4 } else { 0.2 }
+    /// Whether every level needs some XP.
+    pub fn is_valid(&self) -> bool {
+        match self {
+            XpCurve::Constant(xp) => *xp > 0,
+            XpCurve::Linear { base, .. } => *base > 0,
+            XpCurve::Table(table) => !table.is_empty() && table.iter().all(|xp| *xp > 0),
+        }
     }
 }
 
-const XP_PER_LEVEL: u32 = 10;
-
 #[derive(Component, Reflect, Default)]
 pub struct XpGather {
     pub range: f32,
     pub acceleration: f32,
 }
 
-impl IsSkill for XpGather {
-    fn skill() -> Skill {
-        Skill::XpGather
-    }
-}
-
 #[derive(Component)]
 pub struct XpGatherState {
     pub xp: u32,
+    /// XP at which the current gather level started.
+    level_xp: u32,
     gather_level: u32,
+    /// Levels granted without XP, e.g. by chests.
+    bonus_levels: u32,
     player_level: u32,
 }
 
 impl XpGatherState {
     pub fn get_gather_level(&self) -> u32 {
-        self.gather_level + 1
+        self.gather_level + self.bonus_levels + 1
     }
 
     pub fn get_player_level(&self) -> u32 {
@@ -94,67 +84,126 @@ impl XpGatherState {
         self.player_level += 1;
     }
 
-    pub fn gather(&mut self, xp: u32, xp_per_level: u32) {
+    /// Level ups gathered but not yet taken, each waiting for an upgrade.
+    pub fn pending_levels(&self) -> u32 {
+        (self.gather_level + self.bonus_levels).saturating_sub(self.player_level)
+    }
+
+    /// Grants a level up without XP.
+    pub fn grant_level(&mut self) {
+        self.bonus_levels += 1;
+    }
+
+    /// Adds `xp`, possibly gaining several levels at once.
+    pub fn gather(&mut self, xp: u32, curve: &XpCurve) {
         self.xp += xp;
-        self.gather_level = self.xp / xp_per_level;
+        loop {
+            let next_level_xp = self
+                .level_xp
+                .saturating_add(curve.xp_for_level(self.gather_level));
+            if self.xp < next_level_xp || next_level_xp == self.level_xp {
+                break;
+            }
+            self.level_xp = next_level_xp;
+            self.gather_level += 1;
+        }
     }
 }
 
-fn init_gather_state(
+pub(crate) fn init_gather_state(
     q_xp_gather: Query<Entity, (With<XpGather>, Without<XpGatherState>)>,
     mut cmd: Commands,
 ) {
     for ent in &q_xp_gather {
         cmd.entity(ent).insert(XpGatherState {
             xp: 0,
+            level_xp: 0,
             gather_level: 0,
+            bonus_levels: 0,
             player_level: 0,
         });
     }
 }
 
-fn gather_xp(
-    time: Res<Time>,
-    q_space: SpatialQuery,
-    mut q_xp_gather: Query<(&Transform, &XpGather, &mut XpGatherState)>,
-    mut q_xp_drop: Query<(Entity, &Transform, &mut LinearVelocity, &XpDrop)>,
-    mut cmd: Commands,
-) {
-    for (tr_gatherer, xp_gather, mut xp_gather_state) in &mut q_xp_gather {
-        for ent in q_space
-            .shape_intersections(
-                &Collider::sphere(xp_gather.range),
-                tr_gatherer.translation,
-                Quat::default(),
-                &SpatialQueryFilter::from_mask([Layer::Building]),
-            )
-            .iter()
-        {
-            if let Ok((ent, tr_xp, mut lin_vel, xp_drop)) = q_xp_drop.get_mut(*ent) {
-                let mut delta = tr_gatherer.translation - tr_xp.translation;
-                if delta.length() < XpDrop::get_height(xp_drop.0) + 1. {
-                    xp_gather_state.gather(xp_drop.0, XP_PER_LEVEL);
-                    cmd.entity(ent).despawn();
-                } else {
-                    lin_vel.y = 0.;
-                    let old_speed = lin_vel.length();
-                    delta.y = 0.;
-                    delta = delta.normalize()
-                        * (old_speed + time.delta_secs() * xp_gather.acceleration);
-                    lin_vel.x = delta.x;
-                    lin_vel.z = delta.z;
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gather_state() -> XpGatherState {
+        XpGatherState {
+            xp: 0,
+            level_xp: 0,
+            gather_level: 0,
+            bonus_levels: 0,
+            player_level: 0,
         }
     }
-}
 
-fn slow_xp_drops(time: Res<Time>, mut q_npc: Query<&mut LinearVelocity, With<XpDrop>>) {
-    for mut lin_vel in &mut q_npc {
-        let speed = lin_vel.length();
-        if speed > f32::EPSILON {
-            let dir = lin_vel.normalize_or_zero();
-            lin_vel.0 = (speed - time.delta_secs() * 5.).max(0.) * dir;
-        }
+    #[test]
+    fn xp_for_level() {
+        assert_eq!(XpCurve::Constant(7).xp_for_level(5), 7);
+        let linear = XpCurve::Linear {
+            base: 10,
+            increment: 5,
+        };
+        assert_eq!(linear.xp_for_level(0), 10);
+        assert_eq!(linear.xp_for_level(3), 25);
+        let linear = XpCurve::Linear {
+            base: 10,
+            increment: u32::MAX,
+        };
+        assert_eq!(linear.xp_for_level(2), u32::MAX);
+        let table = XpCurve::Table(vec![5, 8, 13]);
+        assert_eq!(table.xp_for_level(1), 8);
+        assert_eq!(table.xp_for_level(10), 13);
+    }
+
+    #[test]
+    fn invalid_curves() {
+        assert!(XpCurve::default().is_valid());
+        assert!(!XpCurve::Constant(0).is_valid());
+        assert!(
+            !XpCurve::Linear {
+                base: 0,
+                increment: 5
+            }
+            .is_valid()
+        );
+        assert!(!XpCurve::Table(vec![]).is_valid());
+        assert!(!XpCurve::Table(vec![5, 0]).is_valid());
+    }
+
+    #[test]
+    fn gather_several_levels() {
+        let curve = XpCurve::Table(vec![5, 10]);
+        let mut state = gather_state();
+        state.gather(4, &curve);
+        assert_eq!(state.get_gather_level(), 1);
+        state.gather(21, &curve);
+        // 5 for the first level, then 10 for each of the next two
+        assert_eq!(state.get_gather_level(), 4);
+        assert_eq!(state.pending_levels(), 3);
+        state.gather(9, &curve);
+        assert_eq!(state.get_gather_level(), 4);
+        state.gather(1, &curve);
+        assert_eq!(state.get_gather_level(), 5);
+    }
+
+    #[test]
+    fn pending_levels() {
+        let mut state = gather_state();
+        state.gather(10, &XpCurve::Constant(10));
+        state.grant_level();
+        assert_eq!(state.pending_levels(), 2);
+        state.upgrade_player_level();
+        assert_eq!(state.get_player_level(), 2);
+        assert_eq!(state.pending_levels(), 1);
+    }
+
+    #[test]
+    fn gather_stops_when_curve_needs_no_xp() {
+        let mut state = gather_state();
+        state.gather(100, &XpCurve::Constant(0));
+        assert_eq!(state.get_gather_level(), 1);
     }
 }
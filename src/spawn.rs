@@ -0,0 +1,247 @@
+use std::f32::consts::TAU;
+
+use avian3d::prelude::*;
+use bevy::{ecs::system::SystemParam, math::prelude::*, prelude::*};
+use rand::{distributions::WeightedIndex, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    camera::MainCamera,
+    npc::{NonPlayerCharacter, NpcAssetIndex, SpawnNpc},
+    physics::Layer,
+    player::Player,
+    terrain::GROUND_HALF_EXTENT,
+};
+
+/// How the NPCs of a group are placed, relative to the edge of the visible ground.
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub enum Formation {
+    /// Evenly spaced around the player, `margin` beyond the edge.
+    Ring { margin: f32 },
+    /// Evenly spaced on a segment facing the player from a random direction.
+    Line { margin: f32, length: f32 },
+    /// Scattered in a disk of radius `spread` in a random direction.
+    Cluster { margin: f32, spread: f32 },
+}
+
+/// Visible ground distance used when there is no [`MainCamera`], as in headless apps.
+pub const DEFAULT_VIEW_DISTANCE: f32 = 60.;
+/// Ground points are never further than this from the player, even when the camera
+/// looks above the horizon.
+const MAX_VIEW_DISTANCE: f32 = 300.;
+/// Spawn points are never closer to the player than this beyond the edge, even when the
+/// camera sees little or no ground around the player.
+const MIN_SPAWN_DISTANCE: f32 = 20.;
+/// Number of times an overlapping spawn point is pushed outwards before it is dropped.
+const PLACEMENT_TRIES: usize = 4;
+
+/// Finds spawn points just outside the ground area seen by the [`MainCamera`], that
+/// don't overlap existing colliders.
+#[derive(SystemParam)]
+pub struct SpawnPlacement<'w, 's> {
+    q_camera: Query<'w, 's, (&'static Camera, &'static GlobalTransform), With<MainCamera>>,
+    q_player: Query<'w, 's, &'static Position, With<Player>>,
+    q_space: SpatialQuery<'w, 's>,
+}
+
+impl SpawnPlacement<'_, '_> {
+    /// Ground position of the player, or the origin when there is none.
+    pub fn center(&self) -> Vec2 {
+        self.q_player
+            .single()
+            .map_or(Vec2::ZERO, |pos| Vec2::new(pos.x, pos.z))
+    }
+
+    /// Corners of the ground area seen by the camera, in viewport order.
+    fn visible_ground(&self) -> Option<[Vec2; 4]> {
+        let (camera, camera_gtr) = self.q_camera.single().ok()?;
+        let rect = camera.logical_viewport_rect()?;
+        let corners = [
+            rect.min,
+            Vec2::new(rect.max.x, rect.min.y),
+            rect.max,
+            Vec2::new(rect.min.x, rect.max.y),
+        ];
+        let center = self.center();
+        let mut ground = [Vec2::ZERO; 4];
+        for (corner, pt) in corners.iter().zip(ground.iter_mut()) {
+            let ray = camera.viewport_to_world(camera_gtr, *corner).ok()?;
+            let hit = ray
+                .intersect_plane(Vec3::ZERO, InfinitePlane3d::new(Vec3::Y))
+                .map(|dist| ray.get_point(dist));
+            *pt = match hit {
+                Some(hit) if Vec2::new(hit.x, hit.z).distance(center) <= MAX_VIEW_DISTANCE => {
+                    Vec2::new(hit.x, hit.z)
+                }
+                _ => {
+                    let dir = Vec2::new(ray.direction.x, ray.direction.z).normalize_or_zero();
+                    center + dir * MAX_VIEW_DISTANCE
+                }
+            };
+        }
+        Some(ground)
+    }
+
+    /// Distance from [`Self::center`] to the edge of the visible ground along `dir`, at
+    /// least [`MIN_SPAWN_DISTANCE`].
+    pub fn edge_distance(&self, dir: Vec2) -> f32 {
+        let Some(ground) = self.visible_ground() else {
+            return DEFAULT_VIEW_DISTANCE;
+        };
+        exit_distance(&ground, self.center(), dir).max(MIN_SPAWN_DISTANCE)
+    }
+
+    /// Point `margin` beyond the edge of the visible ground along `dir`.
+    pub fn beyond_edge(&self, dir: Vec2, margin: f32) -> Vec2 {
+        self.center() + dir * (self.edge_distance(dir) + margin)
+    }
+
+    /// Whether a sphere of `radius` resting on the ground at `pt` is on the terrain and
+    /// overlaps no character or building.
+    pub fn is_free(&self, pt: Vec2, radius: f32) -> bool {
+        if pt.abs().max_element() > GROUND_HALF_EXTENT - radius {
+            return false;
+        }
+        self.q_space
+            .shape_intersections(
+                &Collider::sphere(radius),
+                Vec3::new(pt.x, radius + 0.02, pt.y),
+                Quat::default(),
+                &SpatialQueryFilter::from_mask([Layer::Player, Layer::NPC, Layer::Building]),
+            )
+            .is_empty()
+    }
+
+    /// Pushes `pt` away from the player until it is free, also avoiding the points in
+    /// `taken` that are about to be spawned on.
+    pub fn find_free(&self, mut pt: Vec2, radius: f32, taken: &[(Vec2, f32)]) -> Option<Vec2> {
+        let dir = (pt - self.center()).normalize_or(Vec2::X);
+        for _ in 0..PLACEMENT_TRIES {
+            if self.is_free(pt, radius)
+                && taken
+                    .iter()
+                    .all(|(other, other_radius)| pt.distance(*other) > radius + other_radius)
+            {
+                return Some(pt);
+            }
+            pt += dir * 2. * radius;
+        }
+        None
+    }
+
+    /// Spawn commands for `count` NPCs picked from `npcs` by their frequency, placed in
+    /// `formation`. Points that stay blocked are dropped, so fewer NPCs may be returned.
+    /// The points used are added to `taken`, to be avoided by the other groups spawned
+    /// in the same frame.
+    pub fn place_group(
+        &self,
+        formation: Formation,
+        npcs: &[(NpcAssetIndex, &NonPlayerCharacter)],
+        count: usize,
+        taken: &mut Vec<(Vec2, f32)>,
+        rng: &mut impl Rng,
+    ) -> Vec<SpawnNpc> {
+        let Ok(npc_idx) = WeightedIndex::new(npcs.iter().map(|(_, npc)| npc.frequency)) else {
+            return vec![];
+        };
+        let center = self.center();
+        let dir = Vec2::from_angle(rng.gen_range(0.0..TAU));
+        let points: Vec<Vec2> = match formation {
+            Formation::Ring { margin } => (0..count)
+                .map(|i| {
+                    let dir = dir.rotate(Vec2::from_angle(i as f32 * TAU / count as f32));
+                    self.beyond_edge(dir, margin)
+                })
+                .collect(),
+            Formation::Line { margin, length } => {
+                let anchor = self.beyond_edge(dir, margin);
+                let step = if count > 1 {
+                    length / (count - 1) as f32
+                } else {
+                    0.
+                };
+                (0..count)
+                    .map(|i| anchor + (i as f32 * step - length / 2.) * dir.perp())
+                    .collect()
+            }
+            Formation::Cluster { margin, spread } => {
+                let anchor = self.beyond_edge(dir, margin + spread);
+                let circle = Circle::new(spread);
+                (0..count)
+                    .map(|_| anchor + circle.sample_interior(rng))
+                    .collect()
+            }
+        };
+
+        let mut spawns = vec![];
+        for pt in points {
+            let (npc_index, npc) = npcs[npc_idx.sample(rng)];
+            let Some(pt) = self.find_free(pt, npc.radius, taken) else {
+                continue;
+            };
+            taken.push((pt, npc.radius));
+            spawns.push(SpawnNpc {
+                character: npc.clone(),
+                npc_index,
+                location: pt,
+            });
+        }
+        if spawns.len() < count {
+            debug!(
+                "Dropped {} of {count} NPCs with no free spawn point around {center}.",
+                count - spawns.len()
+            );
+        }
+        spawns
+    }
+}
+
+/// Distance from `origin` along `dir` to where it leaves the convex `polygon`, or 0 when
+/// it doesn't cross it ahead.
+fn exit_distance(polygon: &[Vec2], origin: Vec2, dir: Vec2) -> f32 {
+    let mut dist: f32 = 0.;
+    for (idx, a) in polygon.iter().enumerate() {
+        let edge = polygon[(idx + 1) % polygon.len()] - *a;
+        let denom = dir.perp_dot(edge);
+        if denom.abs() < f32::EPSILON {
+            continue;
+        }
+        let w = *a - origin;
+        let (t, s) = (w.perp_dot(edge) / denom, w.perp_dot(dir) / denom);
+        if t > 0. && (0. ..=1.).contains(&s) {
+            dist = dist.max(t);
+        }
+    }
+    dist
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: [Vec2; 4] = [
+        Vec2::new(-10., -10.),
+        Vec2::new(10., -10.),
+        Vec2::new(10., 10.),
+        Vec2::new(-10., 10.),
+    ];
+
+    #[test]
+    fn exit_distance_in_polygon() {
+        assert_eq!(exit_distance(&SQUARE, Vec2::ZERO, Vec2::X), 10.);
+        assert_eq!(exit_distance(&SQUARE, Vec2::ZERO, Vec2::NEG_Y), 10.);
+        assert_eq!(exit_distance(&SQUARE, Vec2::new(5., 0.), Vec2::NEG_X), 15.);
+        let diagonal = exit_distance(&SQUARE, Vec2::ZERO, Vec2::ONE.normalize());
+        assert!((diagonal - 200_f32.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn exit_distance_out_of_polygon() {
+        // the ground seen is all ahead, or all behind the origin
+        assert_eq!(exit_distance(&SQUARE, Vec2::new(20., 0.), Vec2::X), 0.);
+        assert_eq!(exit_distance(&SQUARE, Vec2::new(-20., 0.), Vec2::X), 30.);
+        assert_eq!(exit_distance(&SQUARE, Vec2::new(20., 20.), Vec2::NEG_X), 0.);
+        // a degenerate polygon, as when the camera looks straight along the ground
+        assert_eq!(exit_distance(&[Vec2::ZERO; 4], Vec2::ZERO, Vec2::X), 0.);
+    }
+}
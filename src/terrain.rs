@@ -2,7 +2,7 @@ use avian3d::prelude::*;
 use bevy::prelude::*;
 
 use crate::{
-    app::{AppState, InGame},
+    app::{AppState, Headless, InGame},
     physics::Layer,
 };
 
@@ -23,6 +23,9 @@ impl Plugin for TerrainPlugin {
     }
 }
 
+/// Half the side of the square ground, centered at the origin.
+pub const GROUND_HALF_EXTENT: f32 = 500.;
+
 #[derive(Resource, Default, Reflect)]
 #[reflect(Resource)]
 pub struct Terrain {
@@ -33,22 +36,14 @@ fn setup_terrain(
     mut terrain: ResMut<Terrain>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    headless: Option<Res<Headless>>,
     mut cmd: Commands,
 ) {
-    let ground_size = Vec3::new(1000.0, 1.0, 1000.0);
-    let material = materials.add(StandardMaterial {
-        base_color: bevy::color::palettes::css::SILVER.into(),
-        metallic: 0.0,
-        perceptual_roughness: 0.8,
-        reflectance: 0.2,
-        ..default()
-    });
+    let ground_size = Vec3::new(2. * GROUND_HALF_EXTENT, 1.0, 2. * GROUND_HALF_EXTENT);
 
     terrain.ground = Some({
         let id = cmd
             .spawn((
-                Mesh3d(meshes.add(Cuboid::new(ground_size.x, ground_size.y, ground_size.z))),
-                MeshMaterial3d(material),
                 Transform::from_xyz(0.0, -ground_size.y / 2., 0.0),
                 RigidBody::Static,
                 Collider::cuboid(ground_size.x, ground_size.y, ground_size.z),
@@ -58,6 +53,19 @@ fn setup_terrain(
             .id();
         cmd.entity(id)
             .insert(Name::new(format!("Terrain ({id:?})")));
+        if headless.is_none() {
+            let material = materials.add(StandardMaterial {
+                base_color: bevy::color::palettes::css::SILVER.into(),
+                metallic: 0.0,
+                perceptual_roughness: 0.8,
+                reflectance: 0.2,
+                ..default()
+            });
+            cmd.entity(id).insert((
+                Mesh3d(meshes.add(Cuboid::new(ground_size.x, ground_size.y, ground_size.z))),
+                MeshMaterial3d(material),
+            ));
+        }
         id
     });
 
@@ -1,15 +1,17 @@
 use bevy::{
-    color::palettes::css::{AQUAMARINE, GOLD, ORANGE_RED, YELLOW},
+    color::palettes::css::{AQUAMARINE, DARK_VIOLET, GOLD, ORANGE_RED, YELLOW},
     diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
     prelude::*,
 };
 use rand::prelude::*;
 
 use crate::{
-    app::{AppState, InGame, RunState},
-    player::Player,
+    app::{AppState, InGame, RunSeed, RunState, RunStats},
+    boss::Boss,
+    player::{Player, PlayerIntent},
     skills::{
-        SkillUpgradeOptions, Skills,
+        MAX_UPGRADE_OFFERS, Rarity, Skill, SkillUpgradeOptions, Skills, Upgrade, UpgradeActions,
+        UpgradeChoice,
         health::{Health, MaxHealth},
         xp::XpGatherState,
     },
@@ -27,6 +29,7 @@ impl Plugin for MainUiPlugin {
             (
                 setup_fps_ui,
                 setup_top_bar_ui,
+                setup_boss_bar_ui,
                 setup_upgrade_ui.before(setup_paused_ui),
                 setup_paused_ui,
             ),
@@ -39,13 +42,21 @@ impl Plugin for MainUiPlugin {
                 update_run_time_ui,
                 update_app_state_ui,
                 update_npcs_ui,
+                update_boss_bar_ui,
             )
                 .run_if(in_state(InGame)),
         )
-        .add_systems(OnEnter(AppState::Upgrade), init_skill_upgrade_ui)
+        .init_resource::<BanishMode>()
         .add_systems(
             Update,
-            update_skill_upgrade_ui.run_if(in_state(AppState::Upgrade)),
+            (
+                (init_skill_upgrade_ui, init_upgrade_action_ui)
+                    .run_if(resource_changed::<SkillUpgradeOptions>),
+                update_skill_upgrade_ui,
+                update_upgrade_action_ui,
+            )
+                .chain()
+                .run_if(in_state(AppState::Upgrade)),
         );
     }
 }
@@ -321,6 +332,90 @@ fn update_npcs_ui(
     }
 }
 
+// boss bar
+
+#[derive(Component)]
+struct BossBar;
+
+#[derive(Component)]
+struct BossNameText;
+
+#[derive(Component)]
+struct BossHpFill;
+
+fn setup_boss_bar_ui(mut cmd: Commands) {
+    cmd.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(90.0),
+            left: Val::Percent(25.0),
+            width: Val::Percent(50.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        Visibility::Hidden,
+        BossBar,
+        MainUi,
+        StateScoped(InGame),
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            Text::default(),
+            TextFont {
+                font_size: 24.0,
+                ..default()
+            },
+            BossNameText,
+        ));
+        parent
+            .spawn((
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Px(16.0),
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                BorderColor(Color::BLACK),
+                BackgroundColor(BUTTON_NORMAL_COLOR),
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Node {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BackgroundColor(DARK_VIOLET.into()),
+                    BossHpFill,
+                ));
+            });
+    });
+}
+
+fn update_boss_bar_ui(
+    q_boss: Query<(&Boss, &Health, &MaxHealth)>,
+    mut q_bar: Query<&mut Visibility, With<BossBar>>,
+    mut q_name: Query<&mut Text, With<BossNameText>>,
+    mut q_fill: Query<&mut Node, With<BossHpFill>>,
+) {
+    let Ok(mut visibility) = q_bar.single_mut() else {
+        return;
+    };
+    let Some((boss, health, max_health)) = q_boss.iter().next() else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Inherited;
+    if let Ok(mut txt_name) = q_name.single_mut() {
+        txt_name.0.clone_from(&boss.name);
+    }
+    if let Ok(mut fill) = q_fill.single_mut() {
+        let fraction = (health.0 / max_health.max_hp as f32).clamp(0., 1.);
+        fill.width = Val::Percent(100. * fraction);
+    }
+}
+
 // FPS
 
 fn setup_fps_ui(mut cmd: Commands) {
@@ -352,17 +447,17 @@ fn update_fps(diagnostics: Res<DiagnosticsStore>, mut q_fps_txt: Query<&mut Text
         return;
     };
     let mut fps = 0.0;
-    if let Some(fps_diagnostic) = diagnostics.get(&FrameTimeDiagnosticsPlugin::FPS) {
-        if let Some(fps_smoothed) = fps_diagnostic.smoothed() {
-            fps = fps_smoothed;
-        }
+    if let Some(fps_diagnostic) = diagnostics.get(&FrameTimeDiagnosticsPlugin::FPS)
+        && let Some(fps_smoothed) = fps_diagnostic.smoothed()
+    {
+        fps = fps_smoothed;
     }
     txt_fps.0 = format!("{fps:.0}");
 }
 
 // paused & game over panel
 
-fn setup_paused_ui(mut cmd: Commands) {
+fn setup_paused_ui(mut cmd: Commands, seed: Res<RunSeed>) {
     cmd.spawn((
         Node {
             position_type: PositionType::Absolute,
@@ -408,6 +503,22 @@ fn setup_paused_ui(mut cmd: Commands) {
                     },
                     AppStateText(AppState::Menu),
                 ));
+                parent.spawn((
+                    Text::default(),
+                    Node {
+                        margin: UiRect::horizontal(Val::Px(50.)),
+                        ..default()
+                    },
+                    TextFont {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                    TextLayout {
+                        justify: JustifyText::Center,
+                        ..default()
+                    },
+                    RunSummaryText,
+                ));
                 parent.spawn((
                     Text::new("press ENTER to continue"),
                     Node {
@@ -423,6 +534,17 @@ fn setup_paused_ui(mut cmd: Commands) {
                         ..default()
                     },
                 ));
+                parent.spawn((
+                    Text::new(format!("seed {}", seed.0)),
+                    Node {
+                        margin: UiRect::bottom(Val::Px(30.)),
+                        ..default()
+                    },
+                    TextFont {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                ));
             });
     });
 }
@@ -433,6 +555,10 @@ struct AppStateRoot;
 #[derive(Component)]
 struct AppStateText(AppState);
 
+/// Results of the run, shown when it is lost or won.
+#[derive(Component)]
+struct RunSummaryText;
+
 const LOSE_STRS: [&str; 10] = [
     "DEAD",
     "DECEASED",
@@ -448,8 +574,12 @@ const LOSE_STRS: [&str; 10] = [
 
 fn update_app_state_ui(
     app_state: Res<State<AppState>>,
+    run_state: Res<RunState>,
+    run_stats: Res<RunStats>,
+    skills: Res<Skills>,
     mut q_app_state_root: Query<&mut Node, With<AppStateRoot>>,
     mut q_txt_app_state: Query<(&mut Text, &mut TextColor, &mut AppStateText)>,
+    mut q_txt_summary: Query<&mut Text, (With<RunSummaryText>, Without<AppStateText>)>,
 ) {
     let Ok(mut node) = q_app_state_root.single_mut() else {
         return;
@@ -459,6 +589,15 @@ fn update_app_state_ui(
         return;
     };
     let state = *app_state.get();
+    if marker.0 != state
+        && let Ok(mut txt_summary) = q_txt_summary.single_mut()
+    {
+        txt_summary.0 = if matches!(state, AppState::Lost | AppState::Won) {
+            run_summary_text(&run_state, &run_stats, &skills)
+        } else {
+            String::new()
+        };
+    }
     match state {
         AppState::Paused => {
             if marker.0 != AppState::Paused {
@@ -491,6 +630,70 @@ fn update_app_state_ui(
     }
 }
 
+fn run_summary_text(run_state: &RunState, run_stats: &RunStats, skills: &Skills) -> String {
+    let all_sec = run_state.run_time.as_secs_f32();
+    let min = (all_sec / 60.) as u32;
+    let sec = all_sec as u32 - min * 60;
+    let mut lines = vec![format!(
+        "time {min:02}:{sec:02}   level {}   XP {}",
+        run_stats.level.max(1),
+        run_stats.xp
+    )];
+
+    let mut kills = run_stats.kills.iter().collect::<Vec<_>>();
+    kills.sort_by(|(n1, k1), (n2, k2)| k2.cmp(k1).then(n1.cmp(n2)));
+    let kills = kills
+        .into_iter()
+        .map(|(npc, kills)| format!("{npc} {kills}"))
+        .collect::<Vec<_>>();
+    lines.push(format!("killed: {}", list_or_none(kills)));
+
+    let skill_name = |skill: &Skill| skills.ui_name(*skill).unwrap_or(skill.name()).to_string();
+    let mut dealt = run_stats.damage_dealt.iter().collect::<Vec<_>>();
+    dealt.sort_by(|(_, d1), (_, d2)| d2.total_cmp(d1));
+    let dealt = dealt
+        .into_iter()
+        .map(|(skill, damage)| {
+            let kills = run_stats
+                .skill_kills
+                .get(skill)
+                .copied()
+                .unwrap_or_default();
+            format!("{} {damage:.0} ({kills} kills)", skill_name(skill))
+        })
+        .collect::<Vec<_>>();
+    lines.push(format!("damage dealt: {}", list_or_none(dealt)));
+
+    let mut taken = run_stats.damage_taken.iter().collect::<Vec<_>>();
+    taken.sort_by(|(_, d1), (_, d2)| d2.total_cmp(d1));
+    let taken = taken
+        .into_iter()
+        .map(|(skill, damage)| format!("{} {damage:.0}", skill_name(skill)))
+        .collect::<Vec<_>>();
+    lines.push(format!("damage taken: {}", list_or_none(taken)));
+
+    let mut equipped = run_stats
+        .equipped
+        .iter()
+        .map(|(upgrade, level)| {
+            let name = skills.upgrade_ui_name(*upgrade).unwrap_or("?");
+            format!("{name} {level}")
+        })
+        .collect::<Vec<_>>();
+    equipped.sort();
+    lines.push(format!("skills: {}", list_or_none(equipped)));
+    lines.push(format!("coins earned: {}", run_stats.currency));
+    lines.join("\n")
+}
+
+fn list_or_none(items: Vec<String>) -> String {
+    if items.is_empty() {
+        "none".to_string()
+    } else {
+        items.join(", ")
+    }
+}
+
 // player skill upgrade menu
 
 fn setup_upgrade_ui(mut cmd: Commands) {
@@ -503,6 +706,7 @@ fn setup_upgrade_ui(mut cmd: Commands) {
             align_items: AlignItems::Center,
             justify_content: JustifyContent::Center,
             flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(50.),
             ..default()
         },
         SkillUpgradeRoot,
@@ -510,20 +714,32 @@ fn setup_upgrade_ui(mut cmd: Commands) {
         StateScoped(InGame),
     ))
     .with_children(|parent| {
-        add_skill_upgrade_button(parent, 0);
-        add_skill_upgrade_button(parent, 1);
-        add_skill_upgrade_button(parent, 2);
+        parent.spawn((
+            Text::default(),
+            TextFont {
+                font_size: 30.0,
+                ..default()
+            },
+            TextColor(GOLD.into()),
+            UpgradesPendingText,
+        ));
+        for index in 0..MAX_UPGRADE_OFFERS {
+            add_skill_upgrade_button(parent, index);
+        }
+        parent
+            .spawn(Node {
+                column_gap: Val::Px(30.),
+                ..default()
+            })
+            .with_children(|parent| {
+                add_upgrade_action_button(parent, UpgradeActionButton::Reroll);
+                add_upgrade_action_button(parent, UpgradeActionButton::Skip);
+                add_upgrade_action_button(parent, UpgradeActionButton::Banish);
+            });
     });
 }
 
 fn add_skill_upgrade_button(parent: &mut ChildSpawnerCommands<'_>, index: usize) {
-    if index > 0 {
-        parent.spawn(Node {
-            height: Val::Px(50.),
-            ..default()
-        });
-    }
-
     parent
         .spawn((
             Button,
@@ -532,9 +748,11 @@ fn add_skill_upgrade_button(parent: &mut ChildSpawnerCommands<'_>, index: usize)
                 justify_content: JustifyContent::Center,
                 min_width: Val::Px(600.),
                 flex_direction: FlexDirection::Column,
+                border: UiRect::all(Val::Px(4.)),
                 ..default()
             },
             BackgroundColor(BUTTON_NORMAL_COLOR),
+            BorderColor(rarity_color(Rarity::Common)),
             BorderRadius::all(Val::Px(20.0)),
             SkillUpgradeButton(index),
         ))
@@ -588,11 +806,42 @@ fn add_skill_upgrade_button(parent: &mut ChildSpawnerCommands<'_>, index: usize)
         });
 }
 
+fn add_upgrade_action_button(parent: &mut ChildSpawnerCommands<'_>, action: UpgradeActionButton) {
+    parent.spawn((
+        Button,
+        Text::default(),
+        Node {
+            padding: UiRect::axes(Val::Px(30.), Val::Px(15.)),
+            ..default()
+        },
+        TextFont {
+            font_size: 20.0,
+            ..default()
+        },
+        TextColor(INFINITE_TEMP_COLOR),
+        BackgroundColor(BUTTON_NORMAL_COLOR),
+        BorderRadius::all(Val::Px(15.0)),
+        action,
+    ));
+}
+
+fn rarity_color(rarity: Rarity) -> Color {
+    match rarity {
+        Rarity::Common => Color::srgb(0.5, 0.5, 0.5),
+        Rarity::Uncommon => Color::srgb(0.3, 0.8, 0.3),
+        Rarity::Rare => Color::srgb(0.25, 0.5, 1.),
+        Rarity::Epic => DARK_VIOLET.into(),
+    }
+}
+
 #[derive(Component)]
-struct SkillUpgradeRoot;
+pub(crate) struct SkillUpgradeRoot;
 
 #[derive(Component)]
-struct SkillUpgradeButton(usize);
+struct UpgradesPendingText;
+
+#[derive(Component)]
+pub(crate) struct SkillUpgradeButton(usize);
 
 #[derive(Component)]
 struct SkillUpgradeText(usize);
@@ -600,72 +849,166 @@ struct SkillUpgradeText(usize);
 #[derive(Component)]
 struct SkillUpgradeDetailsText(usize);
 
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UpgradeActionButton {
+    Reroll,
+    Skip,
+    /// Toggles the [`BanishMode`].
+    Banish,
+}
+
+/// When on, pressing an upgrade button banishes its skill instead of taking the upgrade.
+#[derive(Resource, Default)]
+pub(crate) struct BanishMode(bool);
+
 fn init_skill_upgrade_ui(
     upgrade_options: Res<SkillUpgradeOptions>,
     skills: Res<Skills>,
     mut q_root: Query<&mut Node, With<SkillUpgradeRoot>>,
-    mut q_buttons: Query<(&mut Node, &SkillUpgradeButton), Without<SkillUpgradeRoot>>,
+    mut q_buttons: Query<
+        (&mut Node, &mut BorderColor, &SkillUpgradeButton),
+        Without<SkillUpgradeRoot>,
+    >,
     q_texts: Query<(Entity, &SkillUpgradeText)>,
     q_detail_texts: Query<(Entity, &SkillUpgradeDetailsText), Without<SkillUpgradeText>>,
     mut writer: TextUiWriter,
 ) {
     for (text, marker) in &q_texts {
-        if let Some((skill, level)) = upgrade_options.skills.get(marker.0) {
-            if let Some(skill_name) = skills.skills.get(skill) {
-                *writer.text(text, 0) = skill_name.to_string();
-                *writer.text(text, 1) = format!(" Level {level}");
-            }
+        if let Some((upgrade, level)) = upgrade_options.offers.get(marker.0)
+            && let Some(upgrade_name) = skills.upgrade_ui_name(*upgrade)
+        {
+            *writer.text(text, 0) = upgrade_name.to_string();
+            *writer.text(text, 1) = if level.is_first()
+                && matches!(upgrade, Upgrade::Skill(skill) if skills.evolutions.contains_key(skill))
+            {
+                " Evolution".to_string()
+            } else {
+                format!(" Level {level}")
+            };
         }
     }
     for (text, marker) in &q_detail_texts {
-        if let Some((skill, level)) = upgrade_options.skills.get(marker.0) {
+        if let Some((Upgrade::Skill(skill), level)) = upgrade_options.offers.get(marker.0) {
             if let Some(levels) = skills.upgrades.get(skill) {
                 let mut str = String::new();
-                let spec = level.index(&levels).unwrap();
+                let spec = level.index(levels).unwrap();
                 for (attr, val) in spec {
                     if let Some(attr_meta) = skills.attributes.get(attr) {
                         if let Some(prev_level) = level.prev() {
                             if let Some(prev_spec) = prev_level.index(levels) {
-                                if let Some(val_prev) = prev_spec.get(attr) {
-                                    if let Some(delta) = val.delta(*val_prev) {
-                                        if !delta.is_zero() {
-                                            if !str.is_empty() {
-                                                str.push_str(", ");
-                                            }
-                                            str.push_str(&format!(
-                                                "{}: {delta}",
-                                                attr_meta.ui_name
-                                            ));
-                                        }
+                                // attributes new at this level show their value
+                                if let Some(delta) = prev_spec
+                                    .get(attr)
+                                    .map_or(Some(*val), |val_prev| val.delta(*val_prev))
+                                    && !delta.is_zero()
+                                {
+                                    if !str.is_empty() {
+                                        str.push_str(", ");
                                     }
+                                    str.push_str(&format!(
+                                        "{}: {}",
+                                        attr_meta.ui_name,
+                                        skills.value_ui_text(&delta)
+                                    ));
                                 }
                             }
                         } else {
                             if !str.is_empty() {
                                 str.push_str(", ");
                             }
-                            str.push_str(&format!("{}: {val}", attr_meta.ui_name));
+                            str.push_str(&format!(
+                                "{}: {}",
+                                attr_meta.ui_name,
+                                skills.value_ui_text(val)
+                            ));
                         }
                     }
                 }
-                *writer.text(text, 0) = format!("{str}");
+                // the requirement of an evolution, or of evolving the offered skill
+                if let Some(evolution) = skills.evolution_ui_text(*skill) {
+                    str.push('\n');
+                    str.push_str(&evolution);
+                }
+                *writer.text(text, 0) = str;
+            }
+        } else if let Some((upgrade @ Upgrade::Passive(_), level)) =
+            upgrade_options.offers.get(marker.0)
+        {
+            // passive item levels list the total values
+            if let Some(spec) = skills.upgrade_spec(*upgrade, *level) {
+                *writer.text(text, 0) = format!(
+                    "{}\nPassive, applies to all skills",
+                    skills.spec_ui_text(spec)
+                );
             }
         }
     }
-    for (mut node, button) in &mut q_buttons {
-        if button.0 >= upgrade_options.skills.len() {
-            node.display = Display::None;
-        } else {
+    for (mut node, mut border, button) in &mut q_buttons {
+        if let Some((upgrade, level)) = upgrade_options.offers.get(button.0) {
             node.display = Display::Flex;
+            *border = BorderColor(rarity_color(skills.rarity(*upgrade, *level)));
+        } else {
+            node.display = Display::None;
         }
     }
+    // once the offers are cleared the menu stays hidden, also when it wasn't clicked
     for mut node in &mut q_root {
-        node.display = Display::Flex;
+        node.display = if upgrade_options.offers.is_empty() {
+            Display::None
+        } else {
+            Display::Flex
+        };
+    }
+}
+
+fn init_upgrade_action_ui(
+    upgrade_options: Res<SkillUpgradeOptions>,
+    skills: Res<Skills>,
+    mut banish_mode: ResMut<BanishMode>,
+    q_actions: Query<&UpgradeActions>,
+    mut q_buttons: Query<(
+        &mut Node,
+        &mut Text,
+        &mut BackgroundColor,
+        &UpgradeActionButton,
+    )>,
+    mut q_pending_text: Query<&mut Text, (With<UpgradesPendingText>, Without<UpgradeActionButton>)>,
+) {
+    banish_mode.0 = false;
+    for mut text in &mut q_pending_text {
+        text.0 = match upgrade_options.pending {
+            1 => "1 upgrade remaining".to_string(),
+            pending => format!("{pending} upgrades remaining"),
+        };
+    }
+    let Some(actions) = upgrade_options
+        .entity
+        .and_then(|entity| q_actions.get(entity).ok())
+    else {
+        return;
+    };
+    for (mut node, mut text, mut color, button) in &mut q_buttons {
+        let (name, choice, max) = match button {
+            UpgradeActionButton::Reroll => ("Reroll", UpgradeChoice::Reroll, skills.offers.rerolls),
+            UpgradeActionButton::Skip => ("Skip", UpgradeChoice::Skip, skills.offers.skips),
+            UpgradeActionButton::Banish => {
+                ("Banish", UpgradeChoice::Banish(0), skills.offers.banishes)
+            }
+        };
+        let left = actions.left(&skills.offers, choice).unwrap_or_default();
+        text.0 = format!("{name} ({left})");
+        node.display = if max > 0 {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        *color = BUTTON_NORMAL_COLOR.into();
     }
 }
 
-fn update_skill_upgrade_ui(
-    mut upgrade_options: ResMut<SkillUpgradeOptions>,
+pub(crate) fn update_skill_upgrade_ui(
+    mut intent: ResMut<PlayerIntent>,
+    mut banish_mode: ResMut<BanishMode>,
     mut q_root: Query<&mut Node, With<SkillUpgradeRoot>>,
     mut q_interaction: Query<
         (&Interaction, &mut BackgroundColor, &SkillUpgradeButton),
@@ -674,9 +1017,13 @@ fn update_skill_upgrade_ui(
 ) {
     for (interaction, mut color, SkillUpgradeButton(idx)) in &mut q_interaction {
         match *interaction {
+            Interaction::Pressed if banish_mode.0 => {
+                intent.upgrade = Some(UpgradeChoice::Banish(*idx));
+                banish_mode.0 = false;
+            }
             Interaction::Pressed => {
                 *color = BUTTON_PRESSED_COLOR.into();
-                upgrade_options.selected = upgrade_options.skills.get(*idx).copied();
+                intent.upgrade = Some(UpgradeChoice::Take(*idx));
                 for mut node in &mut q_root {
                     node.display = Display::None;
                 }
@@ -690,3 +1037,37 @@ fn update_skill_upgrade_ui(
         }
     }
 }
+
+pub(crate) fn update_upgrade_action_ui(
+    mut intent: ResMut<PlayerIntent>,
+    mut banish_mode: ResMut<BanishMode>,
+    mut q_interaction: Query<
+        (&Interaction, &mut BackgroundColor, &UpgradeActionButton),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, mut color, button) in &mut q_interaction {
+        match (*interaction, button) {
+            (Interaction::Pressed, UpgradeActionButton::Reroll) => {
+                intent.upgrade = Some(UpgradeChoice::Reroll);
+            }
+            (Interaction::Pressed, UpgradeActionButton::Skip) => {
+                intent.upgrade = Some(UpgradeChoice::Skip);
+            }
+            (Interaction::Pressed, UpgradeActionButton::Banish) => {
+                banish_mode.0 = !banish_mode.0;
+            }
+            (Interaction::Hovered, _) => {
+                *color = BUTTON_HOVERED_COLOR.into();
+            }
+            (Interaction::None, _) => {}
+        }
+        if *interaction != Interaction::Hovered {
+            *color = if *button == UpgradeActionButton::Banish && banish_mode.0 {
+                BUTTON_PRESSED_COLOR.into()
+            } else {
+                BUTTON_NORMAL_COLOR.into()
+            };
+        }
+    }
+}
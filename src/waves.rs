@@ -0,0 +1,362 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadDirectError, io::Reader},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use rand::distributions::{WeightedError, WeightedIndex};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    app::{AppState, RunRng, RunState, update_run_time},
+    boss::Boss,
+    npc::{NPCS_ASSET_PATH, NonPlayerCharactersAsset, NpcConfig},
+    replay::AssetHash,
+    skills::health::die,
+    spawn::{Formation, SpawnPlacement},
+};
+
+pub struct WavesPlugin;
+
+impl Plugin for WavesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<WavesAsset>()
+            .init_asset_loader::<WavesAssetLoader>()
+            .init_resource::<SpawnDirector>()
+            .add_systems(Startup, setup_waves_config)
+            .add_systems(
+                OnTransition {
+                    exited: AppState::Menu,
+                    entered: AppState::Run,
+                },
+                reset_spawn_director,
+            )
+            .add_systems(
+                Update,
+                // on this frame's run time, once its dead are no longer counted as live
+                (spawn_waves, check_win_condition)
+                    .after(update_run_time)
+                    .after(die)
+                    .run_if(in_state(AppState::Run)),
+            );
+    }
+}
+
+pub const WAVES_ASSET_PATH: &str = "all.waves.ron";
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Wave {
+    /// Unique name, keeping track of the groups spawned by the wave when the asset is
+    /// edited during a run.
+    pub name: String,
+    /// Run time in seconds when the wave starts spawning.
+    pub start: f32,
+    /// Run time in seconds when the wave stops spawning.
+    pub end: f32,
+    /// Names of the NPCs to spawn, picked by their `frequency`.
+    pub npcs: Vec<String>,
+    /// Groups spawned per second, the first one at `start`.
+    pub rate: f32,
+    pub group_size: usize,
+    pub formation: Formation,
+    /// Groups are shrunk so that `RunState::live_npcs` stays below this, and wait while
+    /// it is reached, unless one of `npcs` is a boss.
+    pub max_live_npcs: u32,
+}
+
+impl Wave {
+    /// Number of groups the wave should have spawned by `run_time`.
+    fn groups_due(&self, run_time: f32) -> u32 {
+        if run_time < self.start {
+            return 0;
+        }
+        let total = ((self.end - self.start) * self.rate).ceil() as u32;
+        (((run_time - self.start) * self.rate).floor() as u32 + 1).min(total)
+    }
+}
+
+/// When the run is won.
+#[derive(Copy, Clone, Default, Debug, Deserialize)]
+pub enum WinCondition {
+    /// All waves are over and their NPCs dead.
+    #[default]
+    ClearWaves,
+    /// A boss was killed and no other boss is alive.
+    KillBoss,
+}
+
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct WavesAsset {
+    pub waves: Vec<Wave>,
+    #[serde(default)]
+    pub win: WinCondition,
+    #[serde(skip)]
+    pub hash: AssetHash,
+}
+
+impl WavesAsset {
+    /// Whether all waves are over at `run_time`.
+    pub fn is_finished(&self, run_time: f32) -> bool {
+        self.waves.iter().all(|wave| run_time >= wave.end)
+    }
+
+    fn validate(&self, npcs: &NonPlayerCharactersAsset) -> Result<(), WavesAssetLoaderError> {
+        for (idx, wave) in self.waves.iter().enumerate() {
+            if self.waves[..idx]
+                .iter()
+                .any(|other| other.name == wave.name)
+            {
+                return Err(WavesAssetLoaderError::DuplicateName(wave.name.clone()));
+            }
+            if !(wave.start >= 0. && wave.start < wave.end) {
+                return Err(WavesAssetLoaderError::InvalidWindow {
+                    wave: idx,
+                    start: wave.start,
+                    end: wave.end,
+                });
+            }
+            if wave.rate <= 0. || wave.group_size == 0 {
+                return Err(WavesAssetLoaderError::NothingSpawned(idx));
+            }
+            let mut frequencies = vec![];
+            for name in &wave.npcs {
+                let Some((_, npc)) = npcs.get_npc_by_name(name) else {
+                    return Err(WavesAssetLoaderError::UnknownNpc {
+                        wave: idx,
+                        npc: name.clone(),
+                    });
+                };
+                frequencies.push(npc.frequency);
+            }
+            WeightedIndex::new(frequencies)
+                .map_err(|error| WavesAssetLoaderError::InvalidFrequencies { wave: idx, error })?;
+        }
+        Ok(())
+    }
+}
+
+/// Loads `*.waves.ron` files and validates them against the NPCs asset.
+#[derive(Default)]
+pub struct WavesAssetLoader;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum WavesAssetLoaderError {
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse RON: {0}")]
+    RonSpannedError(#[from] ron::error::SpannedError),
+    #[error("Could not load NPCs asset: {0}")]
+    NpcsAsset(#[from] Box<LoadDirectError>),
+    #[error("Several waves named {0:?}")]
+    DuplicateName(String),
+    #[error("Wave {wave} starts at {start}s, but ends at {end}s")]
+    InvalidWindow { wave: usize, start: f32, end: f32 },
+    #[error("Wave {0} has no positive rate or group size")]
+    NothingSpawned(usize),
+    #[error("Wave {wave} spawns unknown NPC {npc:?}")]
+    UnknownNpc { wave: usize, npc: String },
+    #[error("Invalid NPC frequencies in wave {wave}: {error}")]
+    InvalidFrequencies { wave: usize, error: WeightedError },
+}
+
+impl AssetLoader for WavesAssetLoader {
+    type Asset = WavesAsset;
+    type Settings = ();
+    type Error = WavesAssetLoaderError;
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &Self::Settings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut custom_asset = ron::de::from_bytes::<WavesAsset>(&bytes)?;
+        custom_asset.hash = AssetHash::of(&bytes);
+        let npcs = load_context
+            .loader()
+            .immediate()
+            .load::<NonPlayerCharactersAsset>(NPCS_ASSET_PATH)
+            .await
+            .map_err(Box::new)?;
+        custom_asset.validate(npcs.get())?;
+        Ok(custom_asset)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
+    }
+}
+
+/// Spawns the waves of the [`WavesAsset`] as the run time passes.
+#[derive(Resource, Default)]
+pub struct SpawnDirector {
+    pub config: Handle<WavesAsset>,
+    /// Groups spawned so far by each wave, by name.
+    spawned_groups: HashMap<String, u32>,
+}
+
+fn setup_waves_config(mut director: ResMut<SpawnDirector>, asset_server: Res<AssetServer>) {
+    director.config = asset_server.load(WAVES_ASSET_PATH);
+}
+
+fn reset_spawn_director(mut director: ResMut<SpawnDirector>) {
+    director.spawned_groups.clear();
+}
+
+fn spawn_waves(
+    run_state: Res<RunState>,
+    mut director: ResMut<SpawnDirector>,
+    mut rng: ResMut<RunRng>,
+    waves_assets: Res<Assets<WavesAsset>>,
+    npc_config: NpcConfig,
+    placement: SpawnPlacement,
+    mut cmd: Commands,
+) {
+    let director = &mut *director;
+    let Some(waves) = waves_assets.get(&director.config) else {
+        return;
+    };
+    let Some(npcs) = npc_config.get() else {
+        return;
+    };
+    let rng = &mut rng.waves;
+    let run_time = run_state.run_time.as_secs_f32();
+    let mut live_npcs = run_state.live_npcs;
+    // points spawned on by all the groups of this frame
+    let mut taken = vec![];
+
+    for wave in &waves.waves {
+        let due = wave.groups_due(run_time);
+        let mut spawned = director
+            .spawned_groups
+            .get(&wave.name)
+            .copied()
+            .unwrap_or_default();
+        if spawned >= due {
+            continue;
+        }
+        let candidates = wave
+            .npcs
+            .iter()
+            .filter_map(|name| npcs.get_npc_by_name(name))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            error!("No NPCs to spawn for wave {}!", wave.name);
+            director.spawned_groups.insert(wave.name.clone(), due);
+            continue;
+        }
+        // groups with a boss ignore the live cap, and are retried until fully placed
+        let has_boss = candidates.iter().any(|(_, npc)| npc.boss.is_some());
+        while spawned < due {
+            let count = if has_boss {
+                wave.group_size
+            } else {
+                wave.group_size
+                    .min(wave.max_live_npcs.saturating_sub(live_npcs) as usize)
+            };
+            // the group waits for NPCs to die
+            if count == 0 {
+                break;
+            }
+            let placed = taken.len();
+            let spawns = placement.place_group(wave.formation, &candidates, count, &mut taken, rng);
+            if has_boss && spawns.len() < count {
+                taken.truncate(placed);
+                break;
+            }
+            spawned += 1;
+            for spawn in spawns {
+                live_npcs += 1;
+                cmd.queue(spawn);
+            }
+        }
+        director.spawned_groups.insert(wave.name.clone(), spawned);
+    }
+}
+
+fn check_win_condition(
+    run_state: Res<RunState>,
+    director: Res<SpawnDirector>,
+    waves_assets: Res<Assets<WavesAsset>>,
+    q_boss: Query<(), With<Boss>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let Some(waves) = waves_assets.get(&director.config) else {
+        return;
+    };
+    let won = match waves.win {
+        WinCondition::ClearWaves => {
+            run_state.live_npcs == 0 && waves.is_finished(run_state.run_time.as_secs_f32())
+        }
+        WinCondition::KillBoss => run_state.bosses_killed > 0 && q_boss.is_empty(),
+    };
+    if won {
+        next_state.set(AppState::Won);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assets() -> (WavesAsset, NonPlayerCharactersAsset) {
+        (
+            ron::de::from_bytes(include_bytes!("../assets/all.waves.ron")).unwrap(),
+            ron::de::from_bytes(include_bytes!("../assets/all.npcs.ron")).unwrap(),
+        )
+    }
+
+    #[test]
+    fn shipped_waves_are_valid() {
+        let (waves, npcs) = assets();
+        waves.validate(&npcs).unwrap();
+    }
+
+    #[test]
+    fn wave_errors() {
+        let (mut waves, npcs) = assets();
+        waves.waves[0].end = waves.waves[0].start;
+        assert!(matches!(
+            waves.validate(&npcs),
+            Err(WavesAssetLoaderError::InvalidWindow { wave: 0, .. })
+        ));
+
+        let (mut waves, npcs) = assets();
+        waves.waves[1].name = waves.waves[0].name.clone();
+        assert!(matches!(
+            waves.validate(&npcs),
+            Err(WavesAssetLoaderError::DuplicateName(_))
+        ));
+
+        let (mut waves, npcs) = assets();
+        waves.waves[0].rate = 0.;
+        assert!(matches!(
+            waves.validate(&npcs),
+            Err(WavesAssetLoaderError::NothingSpawned(0))
+        ));
+
+        let (mut waves, npcs) = assets();
+        waves.waves[0].npcs.push("Dragon".to_string());
+        assert!(matches!(
+            waves.validate(&npcs),
+            Err(WavesAssetLoaderError::UnknownNpc { wave: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn groups_due() {
+        let (waves, _) = assets();
+        let wave = Wave {
+            start: 10.,
+            end: 20.,
+            rate: 0.5,
+            ..waves.waves[0].clone()
+        };
+        assert_eq!(wave.groups_due(9.9), 0);
+        assert_eq!(wave.groups_due(10.), 1);
+        assert_eq!(wave.groups_due(13.), 2);
+        assert_eq!(wave.groups_due(100.), 5);
+    }
+}
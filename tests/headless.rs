@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use avian3d::prelude::*;
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use swarm_hole::{
+    app::{AppState, RunSeed, RunState, RunStats, build_headless_app, run_headless},
+    player::{Player, PlayerControl},
+    replay::{Replay, ReplayPlayback, ReplayRecorder},
+    skills::health::{DamageType, TakeDamageEvent},
+};
+
+const TIMESTEP: f64 = 1. / 64.;
+
+fn headless_app(seed: u64) -> App {
+    let mut app = build_headless_app(Duration::from_secs_f64(TIMESTEP));
+    app.insert_resource(RunSeed(seed));
+    app.world_mut().resource_mut::<ReplayRecorder>().path = None;
+    app
+}
+
+fn player_position(app: &mut App) -> Vec3 {
+    app.world_mut()
+        .query_filtered::<&Position, With<Player>>()
+        .single(app.world())
+        .unwrap()
+        .0
+}
+
+fn assert_same_run(app: &mut App, other: &mut App) {
+    assert_eq!(
+        app.world().resource::<RunState>().run_time,
+        other.world().resource::<RunState>().run_time
+    );
+    let (stats, other_stats) = (
+        app.world().resource::<RunStats>(),
+        other.world().resource::<RunStats>(),
+    );
+    assert_eq!(stats.level, other_stats.level);
+    assert_eq!(stats.xp, other_stats.xp);
+    assert_eq!(stats.kills, other_stats.kills);
+    assert_eq!(stats.equipped, other_stats.equipped);
+    assert_eq!(stats.damage_dealt, other_stats.damage_dealt);
+    assert_eq!(stats.damage_taken, other_stats.damage_taken);
+    assert_eq!(player_position(app), player_position(other));
+}
+
+#[test]
+fn bot_survives_the_first_waves() {
+    let mut app = headless_app(7);
+    let state = run_headless(&mut app, Duration::from_secs(20)).unwrap();
+    assert_eq!(state, AppState::Run);
+    let world = app.world();
+    assert!(world.resource::<RunState>().run_time >= Duration::from_secs(20));
+    assert!(world.resource::<RunStats>().kills.values().sum::<u32>() > 0);
+}
+
+#[test]
+fn same_seed_same_run() {
+    let max_run_time = Duration::from_secs(60);
+    let mut app = headless_app(11);
+    let state = run_headless(&mut app, max_run_time).unwrap();
+    let mut other = headless_app(11);
+    assert_eq!(run_headless(&mut other, max_run_time).unwrap(), state);
+    assert_same_run(&mut app, &mut other);
+}
+
+#[test]
+fn replay_plays_back_the_recorded_run() {
+    let max_run_time = Duration::from_secs(60);
+    let mut app = headless_app(5);
+    let state = run_headless(&mut app, max_run_time).unwrap();
+    let recorded = &app.world().resource::<ReplayRecorder>().replay;
+    assert!(!recorded.upgrades.is_empty());
+    let replay: Replay = ron::de::from_str(&ron::ser::to_string(recorded).unwrap()).unwrap();
+
+    let mut playback = headless_app(replay.seed);
+    playback.insert_resource(PlayerControl::Replay(ReplayPlayback::new(replay)));
+    // differs from the recorded frame times, to tell when it is restored
+    let frame_time = Duration::from_millis(10);
+    playback.insert_resource(TimeUpdateStrategy::ManualDuration(frame_time));
+    assert_eq!(run_headless(&mut playback, max_run_time).unwrap(), state);
+    assert_same_run(&mut app, &mut playback);
+
+    // out of the run, the time no longer follows the recorded frame times
+    playback
+        .world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::Menu);
+    playback.update();
+    assert!(matches!(
+        playback.world().resource::<TimeUpdateStrategy>(),
+        TimeUpdateStrategy::ManualDuration(duration) if *duration == frame_time
+    ));
+}
+
+#[test]
+fn knockback_moves_the_target() {
+    let max_run_time = Duration::from_secs(1);
+    let mut app = headless_app(3);
+    run_headless(&mut app, max_run_time).unwrap();
+    let mut other = headless_app(3);
+    run_headless(&mut other, max_run_time).unwrap();
+    assert_eq!(player_position(&mut app), player_position(&mut other));
+
+    let player = app
+        .world_mut()
+        .query_filtered::<Entity, With<Player>>()
+        .single(app.world())
+        .unwrap();
+    let knockback = Vec3::new(3., 0., 0.);
+    app.world_mut().send_event(TakeDamageEvent {
+        target: player,
+        source: player,
+        skill: None,
+        damage: 0.,
+        kind: DamageType::Physical,
+        crit: false,
+        knockback,
+    });
+    for _ in 0..4 {
+        app.update();
+        other.update();
+    }
+    let offset = player_position(&mut app) - player_position(&mut other);
+    assert!(
+        offset.distance(knockback) < 0.5,
+        "knocked back by {offset} instead of {knockback}"
+    );
+}